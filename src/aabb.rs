@@ -32,7 +32,12 @@ impl Aabb {
     //     true
     // }
 
-    pub fn hit(&self, r: &Ray, mut t_min: f64, mut t_max: f64) -> bool {
+    pub fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> bool {
+        self.clip(r, t_min, t_max).is_some()
+    }
+
+    // The part of [t_min, t_max] the ray spends inside the box, if any.
+    pub fn clip(&self, r: &Ray, mut t_min: f64, mut t_max: f64) -> Option<(f64, f64)> {
         for a in 0..3 {
             let inv_d = 1.0 / r.direction()[a];
             let mut t0 = (self.minimum[a] - r.origin()[a]) * inv_d;
@@ -43,10 +48,10 @@ impl Aabb {
             t_min = f64::max(t0, t_min);
             t_max = f64::min(t1, t_max);
             if t_max <= t_min {
-                return false;
+                return None;
             }
         }
-        true
+        Some((t_min, t_max))
     }
 }
 
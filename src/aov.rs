@@ -0,0 +1,164 @@
+use exr::prelude::*;
+use image::{ImageResult, Rgb, RgbImage};
+
+use crate::{
+    film::{buffer_to_image, Film},
+    sampler::mix_bits,
+    vec3::{Color, Vec3},
+};
+
+// Auxiliary render passes taken from the first hit of each camera ray.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aov {
+    Beauty,
+    Depth,
+    Normal,
+    Albedo,
+    ObjectId,
+    MaterialId,
+    Motion,
+}
+
+impl Aov {
+    pub fn name(self) -> &'static str {
+        match self {
+            Aov::Beauty => "beauty",
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::Albedo => "albedo",
+            Aov::ObjectId => "object_id",
+            Aov::MaterialId => "material_id",
+            Aov::Motion => "motion",
+        }
+    }
+
+    // 8 bit preview of the pass, for viewers without EXR support.
+    pub fn to_image(self, film: &Film) -> RgbImage {
+        let (w, h) = (film.width, film.height);
+        match self {
+            Aov::Beauty => film.to_image(),
+            Aov::Albedo => buffer_to_image(w, h, &film.albedo_buffer()),
+            Aov::Normal => RgbImage::from_fn(w, h, |x, y| {
+                let n = film.pixel(x, y).normal();
+                to_rgb8_linear((n + Vec3::new(1.0, 1.0, 1.0)) * 0.5)
+            }),
+            Aov::Depth => {
+                // Near is white, far and background fade to black.
+                let depth = film.depth_buffer();
+                let max = depth
+                    .iter()
+                    .filter(|d| d.is_finite())
+                    .fold(0.0, |a: f64, b| a.max(*b));
+                RgbImage::from_fn(w, h, |x, y| {
+                    let d = depth[(y * w + x) as usize];
+                    let v = if d.is_finite() && max > 0.0 {
+                        1.0 - d / max
+                    } else {
+                        0.0
+                    };
+                    to_rgb8_linear(Color::new(v, v, v))
+                })
+            }
+            Aov::ObjectId | Aov::MaterialId => {
+                let ids = if self == Aov::ObjectId {
+                    film.object_id_buffer()
+                } else {
+                    film.material_id_buffer()
+                };
+                RgbImage::from_fn(w, h, |x, y| id_color(ids[(y * w + x) as usize]))
+            }
+            Aov::Motion => {
+                // Encoded around mid grey, scaled by the longest vector.
+                let motion = film.motion_buffer();
+                let max = motion.iter().fold(0.0, |a: f64, m| a.max(m.length()));
+                let scale = if max > 0.0 { 0.5 / max } else { 0.0 };
+                RgbImage::from_fn(w, h, |x, y| {
+                    let m = motion[(y * w + x) as usize];
+                    to_rgb8_linear(Color::new(0.5 + m.x * scale, 0.5 + m.y * scale, 0.5))
+                })
+            }
+        }
+    }
+
+    fn channels(self, film: &Film) -> SmallVec<[AnyChannel<FlatSamples>; 4]> {
+        fn f32s(values: impl Iterator<Item = f64>) -> FlatSamples {
+            FlatSamples::F32(values.map(|v| v as f32).collect())
+        }
+        fn vector(names: [&str; 3], v: &[Vec3]) -> SmallVec<[AnyChannel<FlatSamples>; 4]> {
+            let mut channels = SmallVec::new();
+            channels.push(AnyChannel::new(names[0], f32s(v.iter().map(|c| c.x))));
+            channels.push(AnyChannel::new(names[1], f32s(v.iter().map(|c| c.y))));
+            channels.push(AnyChannel::new(names[2], f32s(v.iter().map(|c| c.z))));
+            channels
+        }
+        let mut channels = SmallVec::new();
+        match self {
+            Aov::Beauty => return vector(["R", "G", "B"], &film.color_buffer()),
+            Aov::Albedo => return vector(["R", "G", "B"], &film.albedo_buffer()),
+            Aov::Normal => return vector(["X", "Y", "Z"], &film.normal_buffer()),
+            Aov::Depth => channels.push(AnyChannel::new(
+                "Z",
+                f32s(film.depth_buffer().into_iter()),
+            )),
+            Aov::ObjectId => channels.push(AnyChannel::new(
+                "id",
+                FlatSamples::U32(film.object_id_buffer()),
+            )),
+            Aov::MaterialId => channels.push(AnyChannel::new(
+                "id",
+                FlatSamples::U32(film.material_id_buffer()),
+            )),
+            Aov::Motion => {
+                let motion = film.motion_buffer();
+                channels.push(AnyChannel::new("X", f32s(motion.iter().map(|m| m.x))));
+                channels.push(AnyChannel::new("Y", f32s(motion.iter().map(|m| m.y))));
+            }
+        }
+        channels
+    }
+}
+
+// Writes each pass as `<stem>_<name>.png`.
+pub fn write_aov_images(film: &Film, aovs: &[Aov], stem: &str) -> ImageResult<()> {
+    for aov in aovs {
+        aov.to_image(film)
+            .save(format!("{}_{}.png", stem, aov.name()))?;
+    }
+    Ok(())
+}
+
+// Writes all passes into one multi-layer EXR, one layer per pass, in linear
+// floating point.
+pub fn write_exr_layers(film: &Film, aovs: &[Aov], path: &str) -> exr::error::UnitResult {
+    let size = (film.width as usize, film.height as usize);
+    let layers: Vec<Layer<AnyChannels<FlatSamples>>> = aovs
+        .iter()
+        .map(|aov| {
+            Layer::new(
+                size,
+                LayerAttributes::named(aov.name()),
+                Encoding::FAST_LOSSLESS,
+                AnyChannels::sort(aov.channels(film)),
+            )
+        })
+        .collect();
+    let image = Image::from_layers(
+        ImageAttributes::new(IntegerBounds::from_dimensions(size)),
+        layers,
+    );
+    image.write().to_file(path)
+}
+
+fn to_rgb8_linear(c: Color) -> Rgb<u8> {
+    let q = |v: f64| (256.0 * v.clamp(0.0, 0.999)) as u8;
+    Rgb([q(c.x), q(c.y), q(c.z)])
+}
+
+// Distinct false colour per id, black for "nothing hit".
+fn id_color(id: u32) -> Rgb<u8> {
+    if id == 0 {
+        return Rgb([0, 0, 0]);
+    }
+    let h = mix_bits(id as u64);
+    Rgb([h as u8 | 0x20, (h >> 8) as u8 | 0x20, (h >> 16) as u8 | 0x20])
+}
@@ -0,0 +1,173 @@
+use std::{
+    fs,
+    io::{self, Error, ErrorKind},
+    sync::Arc,
+};
+
+use crate::{
+    material::Material,
+    triangle::TriangleMesh,
+    vec3::{cross, Point3, Vec3},
+};
+
+// Bicubic Bézier patch over u, v in [0, 1], with control points indexed
+// [v][u].
+#[derive(Debug, Clone, Copy)]
+pub struct BezierPatch {
+    pub control: [[Point3; 4]; 4],
+}
+
+impl BezierPatch {
+    pub fn new(control: [[Point3; 4]; 4]) -> BezierPatch {
+        BezierPatch { control }
+    }
+
+    pub fn point(&self, u: f64, v: f64) -> Point3 {
+        let (bu, bv) = (bernstein(u), bernstein(v));
+        let mut p = Point3::default();
+        for (row, wv) in self.control.iter().zip(bv) {
+            for (c, wu) in row.iter().zip(bu) {
+                p += *c * (wu * wv);
+            }
+        }
+        p
+    }
+
+    // Partial derivatives with respect to u and v.
+    pub fn tangents(&self, u: f64, v: f64) -> (Vec3, Vec3) {
+        let (bu, bv) = (bernstein(u), bernstein(v));
+        let (du, dv) = (bernstein_derivative(u), bernstein_derivative(v));
+        let mut dpdu = Vec3::default();
+        let mut dpdv = Vec3::default();
+        for (j, row) in self.control.iter().enumerate() {
+            for (i, c) in row.iter().enumerate() {
+                dpdu += *c * (du[i] * bv[j]);
+                dpdv += *c * (bu[i] * dv[j]);
+            }
+        }
+        (dpdu, dpdv)
+    }
+
+    // Unit normal, following the orientation of u cross v. At degenerate
+    // points, such as where a row of control points collapses to a pole, it
+    // is taken from a point slightly towards the middle of the patch.
+    pub fn normal(&self, u: f64, v: f64) -> Vec3 {
+        for step in [0.0, 1e-4, 1e-3, 1e-2] {
+            let (dpdu, dpdv) =
+                self.tangents(u + step * (0.5 - u).signum(), v + step * (0.5 - v).signum());
+            let n = cross(&dpdu, &dpdv);
+            if n.length_squared() > 1e-20 * dpdu.length_squared() * dpdv.length_squared() {
+                return n.unit_vector();
+            }
+        }
+        Vec3::default()
+    }
+
+    // Segments per side of a tessellation that stays within `tolerance` of the
+    // patch. A cubic's distance from its chord over a segment of parameter
+    // length h is at most 3/4 h^2 times the largest second difference of its
+    // control points.
+    pub fn segments(&self, tolerance: f64) -> usize {
+        let mut second = 0.0f64;
+        for i in 0..4 {
+            for j in 0..2 {
+                let c = &self.control;
+                let along_u = c[i][j] - c[i][j + 1] * 2.0 + c[i][j + 2];
+                let along_v = c[j][i] - c[j + 1][i] * 2.0 + c[j + 2][i];
+                second = second.max(along_u.length()).max(along_v.length());
+            }
+        }
+        ((0.75 * second / tolerance).sqrt().ceil() as usize).clamp(1, 64)
+    }
+}
+
+// Triangles approximating the patches within `tolerance`, each patch split
+// into a grid as fine as its curvature needs. Neighbouring patches may be
+// split differently, leaving cracks narrower than the tolerance. Vertices get
+// the exact normals of the patch and its (u, v).
+pub fn tessellate(
+    patches: &[BezierPatch],
+    tolerance: f64,
+    mat_ptr: Arc<dyn Material + Sync + Send>,
+) -> TriangleMesh {
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    let mut indices = Vec::new();
+    for patch in patches {
+        let n = patch.segments(tolerance);
+        let base = positions.len();
+        for j in 0..=n {
+            for i in 0..=n {
+                let (u, v) = (i as f64 / n as f64, j as f64 / n as f64);
+                positions.push(patch.point(u, v));
+                normals.push(patch.normal(u, v));
+                uvs.push((u, v));
+            }
+        }
+        let at = |i: usize, j: usize| base + j * (n + 1) + i;
+        for j in 0..n {
+            for i in 0..n {
+                indices.push([at(i, j), at(i + 1, j), at(i + 1, j + 1)]);
+                indices.push([at(i, j), at(i + 1, j + 1), at(i, j + 1)]);
+            }
+        }
+    }
+    TriangleMesh::new(positions, indices, mat_ptr)
+        .with_normals(normals)
+        .with_uvs(uvs)
+}
+
+// Reads bicubic patches in the text format of Haines's Standard Procedural
+// Databases (.bpt), as the Utah teapot is usually distributed: the number of
+// patches, then for each the degrees "3 3" and its 16 control points, one
+// "x y z" per line.
+pub fn load_bpt(path: &str) -> io::Result<Vec<BezierPatch>> {
+    let text = fs::read_to_string(path)?;
+    let invalid = |msg: &str| Error::new(ErrorKind::InvalidData, msg.to_string());
+    let mut numbers = text
+        .split_whitespace()
+        .map(|s| s.parse::<f64>().map_err(|_| invalid("expected a number")));
+    let mut next = || {
+        numbers
+            .next()
+            .unwrap_or_else(|| Err(invalid("unexpected end of file")))
+    };
+
+    // Each patch takes its two degrees and 16 control points, so a count the
+    // file can't hold is rejected before anything is read.
+    let count = next()?;
+    let most = text.split_whitespace().count() / 50;
+    if count < 0.0 || count.fract() != 0.0 || count > most as f64 {
+        return Err(invalid("bad patch count"));
+    }
+    let mut patches = Vec::new();
+    for _ in 0..count as usize {
+        if next()? != 3.0 || next()? != 3.0 {
+            return Err(invalid("only bicubic patches are supported"));
+        }
+        let mut control = [[Point3::default(); 4]; 4];
+        for row in control.iter_mut() {
+            for c in row.iter_mut() {
+                *c = Point3::new(next()?, next()?, next()?);
+            }
+        }
+        patches.push(BezierPatch::new(control));
+    }
+    Ok(patches)
+}
+
+fn bernstein(t: f64) -> [f64; 4] {
+    let s = 1.0 - t;
+    [s * s * s, 3.0 * t * s * s, 3.0 * t * t * s, t * t * t]
+}
+
+fn bernstein_derivative(t: f64) -> [f64; 4] {
+    let s = 1.0 - t;
+    [
+        -3.0 * s * s,
+        3.0 * s * s - 6.0 * t * s,
+        6.0 * t * s - 3.0 * t * t,
+        3.0 * t * t,
+    ]
+}
@@ -0,0 +1,195 @@
+use std::sync::Arc;
+
+use crate::{
+    hittable::HitRecord,
+    material::Material,
+    medium::Medium,
+    ray::Ray,
+    sampler::Sampler,
+    texture::Texture,
+    vec3::{cross, dot, Color, Vec3},
+};
+
+// Wraps a material with a tangent-space normal map: red and green map to the
+// u and v tangent directions and blue to the surface normal, each scaled from
+// [0, 1] to [-1, 1]. Load image maps with `ImageTexture::linear`.
+pub struct NormalMap {
+    pub base: Arc<dyn Material + Send + Sync>,
+    pub map: Arc<dyn Texture + Send + Sync>,
+    // Scales the tangential part of the mapped normal.
+    pub strength: f64,
+}
+
+impl NormalMap {
+    pub fn new(
+        base: Arc<dyn Material + Send + Sync>,
+        map: Arc<dyn Texture + Send + Sync>,
+    ) -> NormalMap {
+        NormalMap {
+            base,
+            map,
+            strength: 1.0,
+        }
+    }
+
+    fn shading_normal(&self, rec: &HitRecord) -> Vec3 {
+        let n = rec.normal;
+        let t = rec.dpdu - n * dot(&n, &rec.dpdu);
+        if t.near_zero() {
+            return n;
+        }
+        let t = t.unit_vector();
+        let mut b = cross(&n, &t);
+        if dot(&b, &rec.dpdv) < 0.0 {
+            b = -b;
+        }
+        let c = self.map.value(rec.u, rec.v, &rec.p) * 2.0 - Color::new(1.0, 1.0, 1.0);
+        (t * (c.x * self.strength) + b * (c.y * self.strength) + n * c.z).unit_vector()
+    }
+}
+
+// Wraps a material with a bump map: the surface shades as if displaced along
+// its normal by `scale` times the scalar value of `height`.
+pub struct BumpMap {
+    pub base: Arc<dyn Material + Send + Sync>,
+    pub height: Arc<dyn Texture + Send + Sync>,
+    pub scale: f64,
+}
+
+impl BumpMap {
+    pub fn new(
+        base: Arc<dyn Material + Send + Sync>,
+        height: Arc<dyn Texture + Send + Sync>,
+        scale: f64,
+    ) -> BumpMap {
+        BumpMap {
+            base,
+            height,
+            scale,
+        }
+    }
+
+    fn shading_normal(&self, rec: &HitRecord) -> Vec3 {
+        // Finite differences of the height in u and v; textures defined in
+        // space see the point moved along the matching tangent.
+        const DELTA: f64 = 1e-4;
+        let h = |u: f64, v: f64, p: Vec3| self.scale * self.height.scalar(u, v, &p);
+        let h0 = h(rec.u, rec.v, rec.p);
+        let hu = h(rec.u + DELTA, rec.v, rec.p + rec.dpdu * DELTA);
+        let hv = h(rec.u, rec.v + DELTA, rec.p + rec.dpdv * DELTA);
+        let n = rec.normal;
+        let dpdu = rec.dpdu + n * ((hu - h0) / DELTA);
+        let dpdv = rec.dpdv + n * ((hv - h0) / DELTA);
+        let bumped = cross(&dpdu, &dpdv);
+        if bumped.near_zero() {
+            return n;
+        }
+        let bumped = bumped.unit_vector();
+        if dot(&bumped, &n) < 0.0 {
+            -bumped
+        } else {
+            bumped
+        }
+    }
+}
+
+impl Material for NormalMap {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
+        let shaded = shade(r_in, rec, self.shading_normal(rec));
+        self.base
+            .scatter(r_in, &shaded, attenuation, scattered, sampler)
+            && !leaks(rec, &shaded, scattered)
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Option<Color> {
+        let shaded = shade(r_in, rec, self.shading_normal(rec));
+        if leaks(rec, &shaded, scattered) {
+            return Some(Color::default());
+        }
+        self.base.eval(r_in, &shaded, scattered)
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let shaded = shade(r_in, rec, self.shading_normal(rec));
+        self.base.scattering_pdf(r_in, &shaded, scattered)
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.base.albedo(rec)
+    }
+
+    fn medium(&self, wavelength: Option<f64>) -> Option<Medium> {
+        self.base.medium(wavelength)
+    }
+
+    fn dispersive(&self) -> bool {
+        self.base.dispersive()
+    }
+}
+
+impl Material for BumpMap {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
+        let shaded = shade(r_in, rec, self.shading_normal(rec));
+        self.base
+            .scatter(r_in, &shaded, attenuation, scattered, sampler)
+            && !leaks(rec, &shaded, scattered)
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Option<Color> {
+        let shaded = shade(r_in, rec, self.shading_normal(rec));
+        if leaks(rec, &shaded, scattered) {
+            return Some(Color::default());
+        }
+        self.base.eval(r_in, &shaded, scattered)
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let shaded = shade(r_in, rec, self.shading_normal(rec));
+        self.base.scattering_pdf(r_in, &shaded, scattered)
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.base.albedo(rec)
+    }
+
+    fn medium(&self, wavelength: Option<f64>) -> Option<Medium> {
+        self.base.medium(wavelength)
+    }
+
+    fn dispersive(&self) -> bool {
+        self.base.dispersive()
+    }
+}
+
+// The hit record as the wrapped material sees it, with shading normal `n`
+// unless the viewer would be behind it.
+fn shade(r_in: &Ray, rec: &HitRecord, n: Vec3) -> HitRecord {
+    let mut shaded = rec.clone();
+    if dot(r_in.direction(), &n) < 0.0 {
+        shaded.set_shading_normal(n);
+    }
+    shaded
+}
+
+// Whether the shading and geometric normals disagree about which side of the
+// surface `scattered` leaves on. Such a direction, reflected about the shading
+// normal yet pointing into the geometry or transmitted yet pointing out of it,
+// would leak light through the surface, so the path is dropped.
+fn leaks(rec: &HitRecord, shaded: &HitRecord, scattered: &Ray) -> bool {
+    let d = scattered.direction();
+    (dot(d, &rec.geometric_normal) > 0.0) != (dot(d, &shaded.normal) > 0.0)
+}
@@ -1,4 +1,4 @@
-use std::{cmp::Ordering, sync::Arc};
+use std::{cmp::Ordering, error::Error, fmt, sync::Arc};
 
 use crate::{
     aabb::{surrounding_box, Aabb},
@@ -8,6 +8,26 @@ use crate::{
     vec3::Vec3,
 };
 
+// Why a BVH couldn't be built. Objects without a bounding box, such as
+// infinite planes, belong in a `Scene`, which keeps them outside the BVH.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BvhError {
+    Empty,
+    // Index of the object with no bounding box.
+    Unbounded(usize),
+}
+
+impl fmt::Display for BvhError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BvhError::Empty => write!(f, "cannot build a BVH over no objects"),
+            BvhError::Unbounded(i) => write!(f, "object {} has no bounding box", i),
+        }
+    }
+}
+
+impl Error for BvhError {}
+
 #[derive(Clone)]
 pub struct BVHNode {
     left: Arc<dyn Hittable + Send + Sync>,
@@ -16,7 +36,10 @@ pub struct BVHNode {
 }
 
 impl BVHNode {
-    pub fn new(list: &HittableList, time0: f64, time1: f64) -> Self {
+    pub fn new(list: &HittableList, time0: f64, time1: f64) -> Result<Self, BvhError> {
+        if list.objects.is_empty() {
+            return Err(BvhError::Empty);
+        }
         let result = BVHNode {
             left: list.objects[0].clone(),
             right: list.objects[0].clone(),
@@ -35,6 +58,7 @@ impl BVHNode {
         )
     }
 
+    #[allow(clippy::self_named_constructors)]
     pub fn bvh_node(
         mut node: BVHNode,
         mut objects: Vec<Arc<dyn Hittable + Send + Sync>>,
@@ -42,7 +66,16 @@ impl BVHNode {
         end: usize,
         time0: f64,
         time1: f64,
-    ) -> BVHNode {
+    ) -> Result<BVHNode, BvhError> {
+        if end <= start {
+            return Err(BvhError::Empty);
+        }
+        let mut output_box = Aabb::new(Vec3::default(), Vec3::default());
+        for (i, object) in objects.iter().enumerate().take(end).skip(start) {
+            if !object.bounding_box(time0, time1, &mut output_box) {
+                return Err(BvhError::Unbounded(i));
+            }
+        }
         let axis = rand_int(0, 2);
         let comparator = match axis {
             0 => Self::box_x_compare,
@@ -88,7 +121,7 @@ impl BVHNode {
                     mid,
                     time0,
                     time1,
-                ));
+                )?);
                 node.right = Arc::new(BVHNode::bvh_node(
                     node.clone(),
                     objects.clone(),
@@ -96,19 +129,16 @@ impl BVHNode {
                     end,
                     time0,
                     time1,
-                ));
+                )?);
             }
         }
 
         let mut box_left = Aabb::new(Vec3::default(), Vec3::default());
         let mut box_right = Aabb::new(Vec3::default(), Vec3::default());
-        if !node.left.bounding_box(time0, time1, &mut box_left)
-            || !node.right.bounding_box(time0, time1, &mut box_right)
-        {
-            eprintln!("no bounding box in bvh_node constructor")
-        }
+        node.left.bounding_box(time0, time1, &mut box_left);
+        node.right.bounding_box(time0, time1, &mut box_right);
         node.r#box = surrounding_box(box_left, box_right);
-        node
+        Ok(node)
     }
 
     pub fn box_compare(
@@ -116,11 +146,11 @@ impl BVHNode {
         b: Arc<dyn Hittable + Send + Sync>,
         axis: i32,
     ) -> Ordering {
+        // Only called on objects `bvh_node` has checked for bounding boxes.
         let mut box_a = Aabb::new(Vec3::default(), Vec3::default());
         let mut box_b = Aabb::new(Vec3::default(), Vec3::default());
-        if !a.bounding_box(0.0, 0.0, &mut box_a) || !b.bounding_box(0.0, 0.0, &mut box_b) {
-            eprintln!("No bounding box in bvh_node constructor.");
-        };
+        a.bounding_box(0.0, 0.0, &mut box_a);
+        b.bounding_box(0.0, 0.0, &mut box_b);
         if box_a.minimum[axis] < box_b.minimum[axis] {
             Ordering::Less
         } else {
@@ -161,10 +191,18 @@ impl Hittable for BVHNode {
         if !self.r#box.hit(r, t_min, t_max) {
             return false;
         };
+        // Both children write into `rec`, so state only some primitives set,
+        // like a mesh's vertex colour, is cleared before each child tries and
+        // put back from the closest hit, or from before, when it misses.
+        let before = rec.vertex_color.take();
         let hit_left = self.left.hit(r, t_min, t_max, rec);
+        let left = rec.vertex_color.take();
         let hit_right = self
             .right
             .hit(r, t_min, if hit_left { rec.t } else { t_max }, rec);
+        if !hit_right {
+            rec.vertex_color = if hit_left { left } else { before };
+        }
         hit_right || hit_left
     }
     fn bounding_box(&self, _time0: f64, _time1: f64, output_box: &mut Aabb) -> bool {
@@ -1,5 +1,6 @@
 use crate::ray::Ray;
 use crate::rtweekend::*;
+use crate::sampler::{sample_unit_disk, Sampler};
 use crate::vec3::*;
 
 pub struct Camera {
@@ -90,13 +91,29 @@ impl Camera {
             time1,
         }
     }
-    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
-        let rd = random_in_unit_disk() * self.lens_radius;
+    // Viewport coordinates (s, t) of a world point, the inverse of `get_ray` for
+    // a pinhole. None for points behind the camera.
+    pub fn project(&self, p: Point3) -> Option<(f64, f64)> {
+        let focus_dist = dot(&(self.origin - self.lower_left_corner), &self.w);
+        let d = p - self.origin;
+        let depth = dot(&d, &-self.w);
+        if depth <= 0.0 {
+            return None;
+        }
+        let on_plane = self.origin + d * (focus_dist / depth) - self.lower_left_corner;
+        Some((
+            dot(&on_plane, &self.horizontal) / self.horizontal.length_squared(),
+            dot(&on_plane, &self.vertical) / self.vertical.length_squared(),
+        ))
+    }
+
+    pub fn get_ray(&self, s: f64, t: f64, sampler: &mut dyn Sampler) -> Ray {
+        let rd = sample_unit_disk(sampler.get_2d()) * self.lens_radius;
         let offset = self.u * rd.x + self.v * rd.y;
         Ray::new(
             self.origin + offset,
             self.lower_left_corner + self.horizontal * s + self.vertical * t - self.origin - offset,
-            rand_double(self.time0, self.time1),
+            self.time0 + (self.time1 - self.time0) * sampler.get_1d(),
         )
     }
 }
@@ -0,0 +1,139 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::{surrounding_box, Aabb},
+    disk::{azimuth, disk_box},
+    hittable::*,
+    material::Material,
+    onb::Onb,
+    ray::Ray,
+    rtweekend::PI,
+    vec3::*,
+};
+
+// Cone over the disk around `base`, narrowing to its apex `height` along
+// `axis`, closed by a base cap unless built `without_cap`. u is the angle
+// around the axis; v is the height on the side and the distance from the axis
+// on the cap.
+#[derive(Clone)]
+pub struct Cone {
+    base: Point3,
+    frame: Onb,
+    radius: f64,
+    height: f64,
+    capped: bool,
+    mat_ptr: Arc<dyn Material + Sync + Send>,
+    id: u32,
+    material_id: u32,
+}
+
+impl Cone {
+    pub fn new(
+        base: Point3,
+        axis: Vec3,
+        radius: f64,
+        height: f64,
+        mat_ptr: Arc<dyn Material + Sync + Send>,
+    ) -> Cone {
+        Cone {
+            base,
+            frame: Onb::build_from_w(&axis),
+            radius,
+            height,
+            capped: true,
+            id: new_object_id(),
+            material_id: material_id(&mat_ptr),
+            mat_ptr,
+        }
+    }
+
+    pub fn without_cap(mut self) -> Cone {
+        self.capped = false;
+        self
+    }
+}
+
+impl Hittable for Cone {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let o = self.frame.to_local(*r.origin() - self.base);
+        let d = self.frame.to_local(*r.direction());
+        // Points on the side satisfy x^2 + y^2 = (k (h - z))^2.
+        let k = self.radius / self.height;
+        let k2 = k * k;
+        let h = self.height - o.z;
+        let a = d.x * d.x + d.y * d.y - k2 * d.z * d.z;
+        let half_b = o.x * d.x + o.y * d.y + k2 * h * d.z;
+        let c = o.x * o.x + o.y * o.y - k2 * h * h;
+        let mut closest: Option<(f64, bool)> = None;
+
+        let mut side_roots = Vec::with_capacity(2);
+        if a != 0.0 {
+            let discriminant = half_b * half_b - a * c;
+            if discriminant >= 0.0 {
+                let sqrtd = discriminant.sqrt();
+                let (t0, t1) = ((-half_b - sqrtd) / a, (-half_b + sqrtd) / a);
+                side_roots.extend([t0.min(t1), t0.max(t1)]);
+            }
+        } else if half_b != 0.0 {
+            side_roots.push(-c / (2.0 * half_b));
+        }
+        for t in side_roots {
+            // The quadric is a double cone; keep the half below the apex.
+            let z = o.z + d.z * t;
+            if t_min <= t && t <= t_max && (0.0..=self.height).contains(&z) {
+                closest = Some((t, false));
+                break;
+            }
+        }
+        if self.capped && d.z != 0.0 {
+            let t = -o.z / d.z;
+            let t_best = closest.map_or(t_max, |c| c.0);
+            let p = o + d * t;
+            if t_min <= t && t <= t_best && p.x * p.x + p.y * p.y <= self.radius * self.radius {
+                closest = Some((t, true));
+            }
+        }
+
+        let (t, cap) = match closest {
+            Some(c) => c,
+            None => return false,
+        };
+        let p = o + d * t;
+        let phi = azimuth(p);
+        let dpdu = Vec3::new(-p.y, p.x, 0.0) * (2.0 * PI);
+        let (normal, v, dpdv) = if cap {
+            let rho = (p.x * p.x + p.y * p.y).sqrt();
+            (
+                Vec3::new(0.0, 0.0, -1.0),
+                rho / self.radius,
+                Vec3::new(phi.cos(), phi.sin(), 0.0) * self.radius,
+            )
+        } else {
+            let radial = Vec3::new(phi.cos(), phi.sin(), 0.0);
+            (
+                (radial + Vec3::new(0.0, 0.0, k)).unit_vector(),
+                p.z / self.height,
+                Vec3::new(0.0, 0.0, self.height) - radial * self.radius,
+            )
+        };
+        rec.t = t;
+        rec.p = r.at(t);
+        rec.set_face_normal(r, &self.frame.local(normal));
+        (rec.u, rec.v) = (phi / (2.0 * PI), v);
+        (rec.dpdu, rec.dpdv) = (self.frame.local(dpdu), self.frame.local(dpdv));
+        rec.mat_ptr = self.mat_ptr.clone();
+        rec.object_id = self.id;
+        rec.material_id = self.material_id;
+        rec.velocity = Vec3::default();
+        true
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64, output_box: &mut Aabb) -> bool {
+        let apex = self.base + self.frame.w * self.height;
+        *output_box = surrounding_box(
+            disk_box(self.base, self.frame.w, self.radius),
+            disk_box(apex, self.frame.w, 0.0),
+        );
+        true
+    }
+}
@@ -0,0 +1,157 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::{surrounding_box, Aabb},
+    hittable::*,
+    ray::Ray,
+    rtweekend::INFINITY,
+    vec3::Point3,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsgOp {
+    Union,
+    Intersection,
+    // The first solid with the second cut out of it.
+    Difference,
+}
+
+impl CsgOp {
+    fn contains(&self, in_a: bool, in_b: bool) -> bool {
+        match self {
+            CsgOp::Union => in_a || in_b,
+            CsgOp::Intersection => in_a && in_b,
+            CsgOp::Difference => in_a && !in_b,
+        }
+    }
+}
+
+// Solid combining two closed objects, or half-spaces such as a `Plane` (the
+// side its normal points away from is inside, as seen by rays that cross it).
+// The crossings of both along a ray are merged, keeping the ones where the ray
+// enters or leaves the combination; surfaces cut out by a difference face the
+// other way.
+pub struct Csg {
+    op: CsgOp,
+    a: Arc<dyn Hittable + Send + Sync>,
+    b: Arc<dyn Hittable + Send + Sync>,
+    id: u32,
+}
+
+impl Csg {
+    pub fn new(
+        op: CsgOp,
+        a: Arc<dyn Hittable + Send + Sync>,
+        b: Arc<dyn Hittable + Send + Sync>,
+    ) -> Csg {
+        Csg {
+            op,
+            a,
+            b,
+            id: new_object_id(),
+        }
+    }
+
+    pub fn union(a: Arc<dyn Hittable + Send + Sync>, b: Arc<dyn Hittable + Send + Sync>) -> Csg {
+        Csg::new(CsgOp::Union, a, b)
+    }
+
+    pub fn intersection(
+        a: Arc<dyn Hittable + Send + Sync>,
+        b: Arc<dyn Hittable + Send + Sync>,
+    ) -> Csg {
+        Csg::new(CsgOp::Intersection, a, b)
+    }
+
+    pub fn difference(
+        a: Arc<dyn Hittable + Send + Sync>,
+        b: Arc<dyn Hittable + Send + Sync>,
+    ) -> Csg {
+        Csg::new(CsgOp::Difference, a, b)
+    }
+}
+
+impl Hittable for Csg {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        match self.hit_all(r, t_min, t_max).into_iter().next() {
+            Some(first) => {
+                *rec = first;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn hit_all(&self, r: &Ray, t_min: f64, t_max: f64) -> Vec<HitRecord> {
+        // Whether the ray starts inside each solid follows from its first
+        // crossing, so the children are followed to infinity. Each crossing
+        // sets the state rather than toggling it, so a crossing lost to
+        // round-off doesn't invert the rest of the ray.
+        let a = self.a.hit_all(r, t_min, INFINITY);
+        let b = self.b.hit_all(r, t_min, INFINITY);
+        let mut in_a = a.first().is_some_and(|h| !h.front_face);
+        let mut in_b = b.first().is_some_and(|h| !h.front_face);
+        let mut inside = self.op.contains(in_a, in_b);
+
+        let mut hits = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() || j < b.len() {
+            let from_a = j == b.len() || (i < a.len() && a[i].t <= b[j].t);
+            let crossing = if from_a {
+                i += 1;
+                in_a = a[i - 1].front_face;
+                &a[i - 1]
+            } else {
+                j += 1;
+                in_b = b[j - 1].front_face;
+                &b[j - 1]
+            };
+            if crossing.t > t_max {
+                break;
+            }
+            let now_inside = self.op.contains(in_a, in_b);
+            if now_inside != inside {
+                let mut rec = crossing.clone();
+                // The normal already faces the ray; only which side is the
+                // outside can change.
+                rec.front_face = now_inside;
+                rec.object_id = self.id;
+                hits.push(rec);
+                inside = now_inside;
+            }
+        }
+        hits
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64, output_box: &mut Aabb) -> bool {
+        let mut box_a = Aabb::new(Point3::default(), Point3::default());
+        let mut box_b = Aabb::new(Point3::default(), Point3::default());
+        let has_a = self.a.bounding_box(time0, time1, &mut box_a);
+        let has_b = self.b.bounding_box(time0, time1, &mut box_b);
+        *output_box = match (self.op, has_a, has_b) {
+            (CsgOp::Union, true, true) => surrounding_box(box_a, box_b),
+            (CsgOp::Intersection, true, true) => overlap(box_a, box_b),
+            (CsgOp::Intersection, true, false) | (CsgOp::Difference, true, _) => box_a,
+            (CsgOp::Intersection, false, true) => box_b,
+            _ => return false,
+        };
+        true
+    }
+}
+
+// The common part of two boxes. Boxes that don't overlap give an inverted box,
+// which no ray hits.
+fn overlap(box0: Aabb, box1: Aabb) -> Aabb {
+    Aabb::new(
+        Point3::new(
+            f64::max(box0.minimum.x, box1.minimum.x),
+            f64::max(box0.minimum.y, box1.minimum.y),
+            f64::max(box0.minimum.z, box1.minimum.z),
+        ),
+        Point3::new(
+            f64::min(box0.maximum.x, box1.maximum.x),
+            f64::min(box0.maximum.y, box1.maximum.y),
+            f64::min(box0.maximum.z, box1.maximum.z),
+        ),
+    )
+}
@@ -0,0 +1,345 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb, hittable::*, hittable_list::HittableList, material::Material, onb::Onb, ray::Ray,
+    vec3::*,
+};
+
+// How the width of a curve is intersected. Flat and Cylinder are ribbons
+// turned to face the ray, which is cheap and fine for strands a few pixels
+// wide at most; a cylinder shades as though it were round. A Ribbon keeps the
+// orientation given by normals at its ends, for grass blades and the like,
+// and is made with `Curve::ribbon`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CurveKind {
+    Flat,
+    Cylinder,
+    Ribbon,
+}
+
+struct CurveCommon {
+    control: [Point3; 4],
+    width: (f64, f64),
+    kind: CurveKind,
+    // Normals of a ribbon at either end, and the angle between them.
+    normals: [Vec3; 2],
+    normal_angle: f64,
+    mat_ptr: Arc<dyn Material + Sync + Send>,
+    id: u32,
+    material_id: u32,
+}
+
+// Cubic Bézier curve whose width varies linearly from one end to the other,
+// for hair, fur and grass. u runs along the curve and v across it, from the
+// side of cross(dp/du, n) where n is the normal, facing the ray unless the
+// curve is an oriented ribbon. A curve can be split into
+// pieces of its parameter range whose boxes hug it more tightly in a
+// `BVHNode`.
+pub struct Curve {
+    common: Arc<CurveCommon>,
+    u0: f64,
+    u1: f64,
+    // Control points of the piece between u0 and u1.
+    segment: [Point3; 4],
+}
+
+impl Curve {
+    pub fn new(
+        control: [Point3; 4],
+        width0: f64,
+        width1: f64,
+        kind: CurveKind,
+        mat_ptr: Arc<dyn Material + Sync + Send>,
+    ) -> Curve {
+        assert!(kind != CurveKind::Ribbon, "a ribbon needs normals");
+        Curve::with_common(
+            control,
+            (width0, width1),
+            kind,
+            [Vec3::default(); 2],
+            mat_ptr,
+        )
+    }
+
+    // A ribbon whose normal turns from `normals[0]` at the start to
+    // `normals[1]` at the end, rather than facing the ray.
+    pub fn ribbon(
+        control: [Point3; 4],
+        width0: f64,
+        width1: f64,
+        normals: [Vec3; 2],
+        mat_ptr: Arc<dyn Material + Sync + Send>,
+    ) -> Curve {
+        let normals = normals.map(|n| n.unit_vector());
+        Curve::with_common(
+            control,
+            (width0, width1),
+            CurveKind::Ribbon,
+            normals,
+            mat_ptr,
+        )
+    }
+
+    fn with_common(
+        control: [Point3; 4],
+        width: (f64, f64),
+        kind: CurveKind,
+        normals: [Vec3; 2],
+        mat_ptr: Arc<dyn Material + Sync + Send>,
+    ) -> Curve {
+        let common = Arc::new(CurveCommon {
+            control,
+            width,
+            kind,
+            normals,
+            normal_angle: dot(&normals[0], &normals[1]).clamp(0.0, 1.0).acos(),
+            id: new_object_id(),
+            material_id: material_id(&mat_ptr),
+            mat_ptr,
+        });
+        Curve::piece(common, 0.0, 1.0)
+    }
+
+    fn piece(common: Arc<CurveCommon>, u0: f64, u1: f64) -> Curve {
+        let c = &common.control;
+        let segment = [
+            blossom(c, u0, u0, u0),
+            blossom(c, u0, u0, u1),
+            blossom(c, u0, u1, u1),
+            blossom(c, u1, u1, u1),
+        ];
+        Curve {
+            common,
+            u0,
+            u1,
+            segment,
+        }
+    }
+
+    // The curve as `segments` pieces of equal parameter range.
+    pub fn split(self, segments: usize) -> HittableList {
+        let mut list = HittableList::default();
+        for i in 0..segments {
+            let s = |i: usize| self.u0 + (self.u1 - self.u0) * i as f64 / segments as f64;
+            list.add(Arc::new(Curve::piece(self.common.clone(), s(i), s(i + 1))));
+        }
+        list
+    }
+
+    fn width(&self, u: f64) -> f64 {
+        let (w0, w1) = self.common.width;
+        w0 + (w1 - w0) * u
+    }
+
+    // Normal of a ribbon at u, interpolated spherically between the ends.
+    fn ribbon_normal(&self, u: f64) -> Vec3 {
+        let [n0, n1] = self.common.normals;
+        let angle = self.common.normal_angle;
+        if angle < 1e-6 {
+            return n0;
+        }
+        (n0 * ((1.0 - u) * angle).sin() + n1 * (u * angle).sin()) / angle.sin()
+    }
+
+    // Searches the curve for the hit nearest the ray origin, with control
+    // points in a frame where the ray runs from the origin along +z, by
+    // splitting it in halves and discarding those whose boxes miss the ray,
+    // down to pieces flat enough to treat as lines. `closest` holds the
+    // largest depth still of interest and u at the hit found so far. A ribbon
+    // seen at a slant along the unit `direction` of the ray looks narrower.
+    fn intersect(
+        &self,
+        cp: [Vec3; 4],
+        (u0, u1): (f64, f64),
+        depth: u32,
+        z_min: f64,
+        direction: Vec3,
+        closest: &mut (f64, Option<f64>),
+    ) {
+        let half_width = 0.5 * self.width(u0).max(self.width(u1));
+        let (lo, hi) = bounds(&cp);
+        if lo.x - half_width > 0.0
+            || hi.x + half_width < 0.0
+            || lo.y - half_width > 0.0
+            || hi.y + half_width < 0.0
+            || hi.z + half_width < z_min
+            || lo.z - half_width > closest.0
+        {
+            return;
+        }
+
+        if depth > 0 {
+            let (left, right) = split_bezier(&cp);
+            let mid = 0.5 * (u0 + u1);
+            self.intersect(left, (u0, mid), depth - 1, z_min, direction, closest);
+            self.intersect(right, (mid, u1), depth - 1, z_min, direction, closest);
+            return;
+        }
+
+        // The ray must pass between the lines through each end perpendicular to
+        // the curve there.
+        let edge = (cp[1].y - cp[0].y) * -cp[0].y + cp[0].x * (cp[0].x - cp[1].x);
+        if edge < 0.0 {
+            return;
+        }
+        let edge = (cp[2].y - cp[3].y) * -cp[3].y + cp[3].x * (cp[3].x - cp[2].x);
+        if edge < 0.0 {
+            return;
+        }
+
+        // Closest point to the ray on the line between the ends.
+        let (dx, dy) = (cp[3].x - cp[0].x, cp[3].y - cp[0].y);
+        let denom = dx * dx + dy * dy;
+        if denom == 0.0 {
+            return;
+        }
+        let w = (-cp[0].x * dx - cp[0].y * dy) / denom;
+        let u = (u0 + (u1 - u0) * w).clamp(u0, u1);
+        let pc = eval_bezier(&cp, w.clamp(0.0, 1.0));
+        let mut width = self.width(u);
+        if self.common.kind == CurveKind::Ribbon {
+            width *= dot(&self.ribbon_normal(u), &direction).abs();
+        }
+        if pc.x * pc.x + pc.y * pc.y > 0.25 * width * width || pc.z < z_min || pc.z > closest.0 {
+            return;
+        }
+        *closest = (pc.z, Some(u));
+    }
+}
+
+impl Hittable for Curve {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let length = r.direction().length();
+        let direction = *r.direction() / length;
+        let frame = Onb::build_from_w(&direction);
+        let cp = self.segment.map(|p| frame.to_local(p - *r.origin()));
+
+        // Splits enough that the pieces are within a twentieth of the width
+        // of straight lines.
+        let mut l0 = 0.0f64;
+        for i in 0..2 {
+            let d = cp[i] - cp[i + 1] * 2.0 + cp[i + 2];
+            l0 = l0.max(d.x.abs()).max(d.y.abs()).max(d.z.abs());
+        }
+        let eps = 0.05 * self.common.width.0.max(self.common.width.1);
+        let depth = if eps > 0.0 && l0 > 0.0 {
+            ((2.0f64.sqrt() * 6.0 * l0 / (8.0 * eps)).log2() / 2.0)
+                .round()
+                .clamp(0.0, 10.0) as u32
+        } else {
+            0
+        };
+
+        let mut closest = (t_max * length, None);
+        self.intersect(
+            cp,
+            (self.u0, self.u1),
+            depth,
+            t_min * length,
+            direction,
+            &mut closest,
+        );
+        let (z, u) = match closest {
+            (z, Some(u)) => (z, u),
+            _ => return false,
+        };
+
+        rec.t = z / length;
+        rec.p = r.at(rec.t);
+        let c = &self.common.control;
+        let dpdu = derivative_bezier(c, u);
+        let tangent = if dpdu.length_squared() > 0.0 {
+            dpdu.unit_vector()
+        } else {
+            (c[3] - c[0]).unit_vector()
+        };
+        // The ribbon's normal faces the ray, unless the ray runs along it, or
+        // is set for an oriented ribbon.
+        let facing = if self.common.kind == CurveKind::Ribbon {
+            self.ribbon_normal(u)
+        } else {
+            -direction
+        };
+        let facing = facing - tangent * dot(&facing, &tangent);
+        if facing.length_squared() == 0.0 {
+            return false;
+        }
+        let normal = facing.unit_vector();
+        let side = cross(&tangent, &normal);
+        let width = self.width(u);
+        let offset = (dot(&(rec.p - eval_bezier(c, u)), &side) / (0.5 * width)).clamp(-1.0, 1.0);
+
+        rec.set_face_normal(r, &normal);
+        if self.common.kind == CurveKind::Cylinder {
+            rec.set_shading_normal(normal * (1.0 - offset * offset).sqrt() + side * offset);
+        }
+        rec.u = u;
+        rec.v = 0.5 + 0.5 * offset;
+        rec.dpdu = dpdu;
+        rec.dpdv = side * width;
+        rec.mat_ptr = self.common.mat_ptr.clone();
+        rec.object_id = self.common.id;
+        rec.material_id = self.common.material_id;
+        rec.velocity = Vec3::default();
+        true
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64, output_box: &mut Aabb) -> bool {
+        let half_width = 0.5 * self.width(self.u0).max(self.width(self.u1));
+        let (lo, hi) = bounds(&self.segment);
+        let pad = Vec3::new(half_width, half_width, half_width);
+        *output_box = Aabb::new(lo - pad, hi + pad);
+        true
+    }
+}
+
+fn bounds(cp: &[Vec3; 4]) -> (Vec3, Vec3) {
+    let mut lo = cp[0];
+    let mut hi = cp[0];
+    for p in &cp[1..] {
+        lo = Vec3::new(lo.x.min(p.x), lo.y.min(p.y), lo.z.min(p.z));
+        hi = Vec3::new(hi.x.max(p.x), hi.y.max(p.y), hi.z.max(p.z));
+    }
+    (lo, hi)
+}
+
+fn lerp(t: f64, a: Vec3, b: Vec3) -> Vec3 {
+    a * (1.0 - t) + b * t
+}
+
+// The curve's polar form: symmetric in its arguments, and equal to the curve
+// at u when all three are u.
+fn blossom(cp: &[Vec3; 4], u0: f64, u1: f64, u2: f64) -> Vec3 {
+    let a = [
+        lerp(u0, cp[0], cp[1]),
+        lerp(u0, cp[1], cp[2]),
+        lerp(u0, cp[2], cp[3]),
+    ];
+    let b = [lerp(u1, a[0], a[1]), lerp(u1, a[1], a[2])];
+    lerp(u2, b[0], b[1])
+}
+
+fn eval_bezier(cp: &[Vec3; 4], u: f64) -> Vec3 {
+    blossom(cp, u, u, u)
+}
+
+fn derivative_bezier(cp: &[Vec3; 4], u: f64) -> Vec3 {
+    let a = [
+        lerp(u, cp[0], cp[1]),
+        lerp(u, cp[1], cp[2]),
+        lerp(u, cp[2], cp[3]),
+    ];
+    let b = [lerp(u, a[0], a[1]), lerp(u, a[1], a[2])];
+    (b[1] - b[0]) * 3.0
+}
+
+fn split_bezier(cp: &[Vec3; 4]) -> ([Vec3; 4], [Vec3; 4]) {
+    let a = [
+        lerp(0.5, cp[0], cp[1]),
+        lerp(0.5, cp[1], cp[2]),
+        lerp(0.5, cp[2], cp[3]),
+    ];
+    let b = [lerp(0.5, a[0], a[1]), lerp(0.5, a[1], a[2])];
+    let mid = lerp(0.5, b[0], b[1]);
+    ([cp[0], a[0], b[0], mid], [mid, b[1], a[2], cp[3]])
+}
@@ -0,0 +1,139 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::{surrounding_box, Aabb},
+    disk::{azimuth, disk_box},
+    hittable::*,
+    material::Material,
+    onb::Onb,
+    ray::Ray,
+    rtweekend::PI,
+    vec3::*,
+};
+
+// Cylinder standing on the disk around `base`, extending `height` along
+// `axis`, closed by caps unless built `without_caps`. On the side u is the
+// angle around the axis and v the height; on the caps v is the distance from
+// the axis.
+#[derive(Clone)]
+pub struct Cylinder {
+    base: Point3,
+    frame: Onb,
+    radius: f64,
+    height: f64,
+    capped: bool,
+    mat_ptr: Arc<dyn Material + Sync + Send>,
+    id: u32,
+    material_id: u32,
+}
+
+enum Part {
+    Side,
+    Bottom,
+    Top,
+}
+
+impl Cylinder {
+    pub fn new(
+        base: Point3,
+        axis: Vec3,
+        radius: f64,
+        height: f64,
+        mat_ptr: Arc<dyn Material + Sync + Send>,
+    ) -> Cylinder {
+        Cylinder {
+            base,
+            frame: Onb::build_from_w(&axis),
+            radius,
+            height,
+            capped: true,
+            id: new_object_id(),
+            material_id: material_id(&mat_ptr),
+            mat_ptr,
+        }
+    }
+
+    // An open tube.
+    pub fn without_caps(mut self) -> Cylinder {
+        self.capped = false;
+        self
+    }
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let o = self.frame.to_local(*r.origin() - self.base);
+        let d = self.frame.to_local(*r.direction());
+        let mut closest: Option<(f64, Part)> = None;
+
+        let a = d.x * d.x + d.y * d.y;
+        if a > 0.0 {
+            let half_b = o.x * d.x + o.y * d.y;
+            let c = o.x * o.x + o.y * o.y - self.radius * self.radius;
+            let discriminant = half_b * half_b - a * c;
+            if discriminant >= 0.0 {
+                let sqrtd = discriminant.sqrt();
+                for t in [(-half_b - sqrtd) / a, (-half_b + sqrtd) / a] {
+                    let z = o.z + d.z * t;
+                    if t_min <= t && t <= t_max && (0.0..=self.height).contains(&z) {
+                        closest = Some((t, Part::Side));
+                        break;
+                    }
+                }
+            }
+        }
+        if self.capped && d.z != 0.0 {
+            for (z, part) in [(0.0, Part::Bottom), (self.height, Part::Top)] {
+                let t = (z - o.z) / d.z;
+                let t_best = closest.as_ref().map_or(t_max, |c| c.0);
+                let p = o + d * t;
+                if t_min <= t && t <= t_best && p.x * p.x + p.y * p.y <= self.radius * self.radius {
+                    closest = Some((t, part));
+                }
+            }
+        }
+
+        let (t, part) = match closest {
+            Some(c) => c,
+            None => return false,
+        };
+        let p = o + d * t;
+        let phi = azimuth(p);
+        let dpdu = Vec3::new(-p.y, p.x, 0.0) * (2.0 * PI);
+        let (normal, v, dpdv) = match part {
+            Part::Side => (
+                Vec3::new(p.x, p.y, 0.0) / self.radius,
+                p.z / self.height,
+                Vec3::new(0.0, 0.0, self.height),
+            ),
+            Part::Bottom | Part::Top => {
+                let rho = (p.x * p.x + p.y * p.y).sqrt();
+                let z = if matches!(part, Part::Top) { 1.0 } else { -1.0 };
+                (
+                    Vec3::new(0.0, 0.0, z),
+                    rho / self.radius,
+                    Vec3::new(phi.cos(), phi.sin(), 0.0) * self.radius,
+                )
+            }
+        };
+        rec.t = t;
+        rec.p = r.at(t);
+        rec.set_face_normal(r, &self.frame.local(normal));
+        (rec.u, rec.v) = (phi / (2.0 * PI), v);
+        (rec.dpdu, rec.dpdv) = (self.frame.local(dpdu), self.frame.local(dpdv));
+        rec.mat_ptr = self.mat_ptr.clone();
+        rec.object_id = self.id;
+        rec.material_id = self.material_id;
+        rec.velocity = Vec3::default();
+        true
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64, output_box: &mut Aabb) -> bool {
+        let top = self.base + self.frame.w * self.height;
+        *output_box = surrounding_box(
+            disk_box(self.base, self.frame.w, self.radius),
+            disk_box(top, self.frame.w, self.radius),
+        );
+        true
+    }
+}
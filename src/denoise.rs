@@ -0,0 +1,109 @@
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+
+use crate::vec3::{Color, Vec3};
+
+pub struct DenoiseSettings {
+    pub iterations: u32,
+    pub sigma_color: f64,
+    pub sigma_normal: f64,
+    pub sigma_albedo: f64,
+}
+
+impl Default for DenoiseSettings {
+    fn default() -> Self {
+        DenoiseSettings {
+            iterations: 5,
+            sigma_color: 0.6,
+            sigma_normal: 0.3,
+            sigma_albedo: 0.1,
+        }
+    }
+}
+
+// B3 spline, the 5 tap kernel of the a-trous wavelet transform.
+const KERNEL: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+// Edge-avoiding a-trous wavelet filter (Dammertz et al. 2010) guided by the
+// first-hit albedo and normal buffers. The colour is divided by the albedo before
+// filtering and multiplied back afterwards, so texture detail is not blurred
+// along with the noise.
+pub fn denoise(
+    width: u32,
+    height: u32,
+    color: &[Color],
+    albedo: &[Color],
+    normal: &[Vec3],
+    settings: &DenoiseSettings,
+) -> Vec<Color> {
+    let mut irradiance: Vec<Color> = color
+        .iter()
+        .zip(albedo)
+        .map(|(c, a)| demodulate(*c, *a))
+        .collect();
+
+    for iteration in 0..settings.iterations {
+        let step = 1i64 << iteration;
+        // The colour edge stopping function tightens as the image gets smoother.
+        let sigma_color = settings.sigma_color / (1 << iteration) as f64;
+        let input = &irradiance;
+        irradiance = (0..(width * height) as usize)
+            .into_par_iter()
+            .map(|p| {
+                let (px, py) = ((p % width as usize) as i64, (p / width as usize) as i64);
+                let mut sum = Color::default();
+                let mut weight_sum = 0.0;
+                for (j, ky) in KERNEL.iter().enumerate() {
+                    for (i, kx) in KERNEL.iter().enumerate() {
+                        let qx = px + (i as i64 - 2) * step;
+                        let qy = py + (j as i64 - 2) * step;
+                        if qx < 0 || qy < 0 || qx >= width as i64 || qy >= height as i64 {
+                            continue;
+                        }
+                        let q = (qy * width as i64 + qx) as usize;
+                        let w = kx
+                            * ky
+                            * edge_weight(input[p], input[q], sigma_color)
+                            * edge_weight(normal[p], normal[q], settings.sigma_normal)
+                            * edge_weight(albedo[p], albedo[q], settings.sigma_albedo);
+                        sum += input[q] * w;
+                        weight_sum += w;
+                    }
+                }
+                if weight_sum > 0.0 {
+                    sum / weight_sum
+                } else {
+                    input[p]
+                }
+            })
+            .collect();
+    }
+
+    irradiance
+        .iter()
+        .zip(albedo)
+        .map(|(e, a)| remodulate(*e, *a))
+        .collect()
+}
+
+fn edge_weight(a: Vec3, b: Vec3, sigma: f64) -> f64 {
+    let d = a - b;
+    (-d.length_squared() / (sigma * sigma)).exp()
+}
+
+const MIN_ALBEDO: f64 = 1e-3;
+
+fn demodulate(c: Color, a: Color) -> Color {
+    Color::new(
+        c.x / a.x.max(MIN_ALBEDO),
+        c.y / a.y.max(MIN_ALBEDO),
+        c.z / a.z.max(MIN_ALBEDO),
+    )
+}
+
+fn remodulate(e: Color, a: Color) -> Color {
+    e * Color::new(
+        a.x.max(MIN_ALBEDO),
+        a.y.max(MIN_ALBEDO),
+        a.z.max(MIN_ALBEDO),
+    )
+}
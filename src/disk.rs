@@ -0,0 +1,99 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb, hittable::*, material::Material, onb::Onb, ray::Ray, rtweekend::PI, vec3::*,
+};
+
+// Flat disk facing along `normal`, or with an inner radius an annulus. u is
+// the angle around the normal and v runs from the inner to the outer edge.
+#[derive(Clone)]
+pub struct Disk {
+    center: Point3,
+    frame: Onb,
+    radius: f64,
+    inner_radius: f64,
+    mat_ptr: Arc<dyn Material + Sync + Send>,
+    id: u32,
+    material_id: u32,
+}
+
+impl Disk {
+    pub fn new(
+        center: Point3,
+        normal: Vec3,
+        radius: f64,
+        mat_ptr: Arc<dyn Material + Sync + Send>,
+    ) -> Disk {
+        Disk::annulus(center, normal, 0.0, radius, mat_ptr)
+    }
+
+    pub fn annulus(
+        center: Point3,
+        normal: Vec3,
+        inner_radius: f64,
+        radius: f64,
+        mat_ptr: Arc<dyn Material + Sync + Send>,
+    ) -> Disk {
+        Disk {
+            center,
+            frame: Onb::build_from_w(&normal),
+            radius,
+            inner_radius,
+            id: new_object_id(),
+            material_id: material_id(&mat_ptr),
+            mat_ptr,
+        }
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let o = self.frame.to_local(*r.origin() - self.center);
+        let d = self.frame.to_local(*r.direction());
+        if d.z == 0.0 {
+            return false;
+        }
+        let t = -o.z / d.z;
+        if t < t_min || t > t_max {
+            return false;
+        }
+        let p = o + d * t;
+        let rho = (p.x * p.x + p.y * p.y).sqrt();
+        if rho > self.radius || rho < self.inner_radius {
+            return false;
+        }
+        let phi = azimuth(p);
+        let width = self.radius - self.inner_radius;
+        rec.t = t;
+        rec.p = r.at(t);
+        rec.set_face_normal(r, &self.frame.w);
+        (rec.u, rec.v) = (phi / (2.0 * PI), (rho - self.inner_radius) / width);
+        rec.dpdu = self.frame.local(Vec3::new(-p.y, p.x, 0.0) * (2.0 * PI));
+        rec.dpdv = self
+            .frame
+            .local(Vec3::new(phi.cos(), phi.sin(), 0.0) * width);
+        rec.mat_ptr = self.mat_ptr.clone();
+        rec.object_id = self.id;
+        rec.material_id = self.material_id;
+        rec.velocity = Vec3::default();
+        true
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64, output_box: &mut Aabb) -> bool {
+        *output_box = disk_box(self.center, self.frame.w, self.radius);
+        true
+    }
+}
+
+// Angle around the local z axis, in [0, 2 pi).
+pub(crate) fn azimuth(p: Vec3) -> f64 {
+    p.y.atan2(p.x).rem_euclid(2.0 * PI)
+}
+
+// Box around a disk with unit normal `axis`, padded so it isn't flat when the
+// disk is axis aligned.
+pub(crate) fn disk_box(center: Point3, axis: Vec3, radius: f64) -> Aabb {
+    let extent = |a: f64| radius * f64::max(0.0, 1.0 - a * a).sqrt() + 1e-4;
+    let e = Vec3::new(extent(axis.x), extent(axis.y), extent(axis.z));
+    Aabb::new(center - e, center + e)
+}
@@ -0,0 +1,285 @@
+use std::sync::Arc;
+
+use image::{Rgb, RgbImage};
+
+use crate::{
+    filter::Filter,
+    rtweekend::clamp,
+    vec3::{Color, Vec3},
+};
+
+// What the camera ray of one sample saw first. `depth` is the distance to the
+// hit (infinite on a miss), `motion` the screen-space motion over the shutter in
+// pixels, and the ids are zero when nothing was hit.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SampleFeatures {
+    pub albedo: Color,
+    pub normal: Vec3,
+    pub depth: f64,
+    pub motion: Vec3,
+    pub object_id: u32,
+    pub material_id: u32,
+}
+
+// Besides the filtered colour, each pixel keeps a plain average of the first-hit
+// features of its own samples, which guide the denoiser and fill the AOV passes.
+// Ids can't be averaged, so a pixel keeps the ids of its first sample.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FilmPixel {
+    pub weighted_sum: Color,
+    pub weight_sum: f64,
+    pub albedo_sum: Color,
+    pub normal_sum: Vec3,
+    pub feature_samples: u32,
+    pub depth_sum: f64,
+    pub motion_sum: Vec3,
+    pub hit_samples: u32,
+    pub object_id: u32,
+    pub material_id: u32,
+}
+
+impl FilmPixel {
+    pub fn color(&self) -> Color {
+        if self.weight_sum > 0.0 {
+            self.weighted_sum / self.weight_sum
+        } else {
+            Color::default()
+        }
+    }
+
+    pub fn albedo(&self) -> Color {
+        if self.feature_samples > 0 {
+            self.albedo_sum / self.feature_samples as f64
+        } else {
+            Color::default()
+        }
+    }
+
+    pub fn normal(&self) -> Vec3 {
+        if self.feature_samples > 0 {
+            self.normal_sum / self.feature_samples as f64
+        } else {
+            Vec3::default()
+        }
+    }
+
+    // Mean distance over the samples that hit something, infinity if none did.
+    pub fn depth(&self) -> f64 {
+        if self.hit_samples > 0 {
+            self.depth_sum / self.hit_samples as f64
+        } else {
+            f64::INFINITY
+        }
+    }
+
+    pub fn motion(&self) -> Vec3 {
+        if self.hit_samples > 0 {
+            self.motion_sum / self.hit_samples as f64
+        } else {
+            Vec3::default()
+        }
+    }
+}
+
+// A rectangle of pixels, [x0, x1) * [y0, y1), in image coordinates (y down).
+#[derive(Debug, Clone, Copy)]
+pub struct TileBounds {
+    pub x0: u32,
+    pub y0: u32,
+    pub x1: u32,
+    pub y1: u32,
+}
+
+// Accumulates filter-weighted samples. Pixel values are only normalised by the
+// summed weights when read back, so contributions from neighbouring tiles that
+// overlap through the filter footprint combine exactly.
+pub struct Film {
+    pub width: u32,
+    pub height: u32,
+    pub filter: Arc<dyn Filter + Send + Sync>,
+    pixels: Vec<FilmPixel>,
+}
+
+impl Film {
+    pub fn new(width: u32, height: u32, filter: Arc<dyn Filter + Send + Sync>) -> Film {
+        Film {
+            width,
+            height,
+            filter,
+            pixels: vec![FilmPixel::default(); (width * height) as usize],
+        }
+    }
+
+    pub fn tiles(&self, tile_size: u32) -> Vec<TileBounds> {
+        let mut tiles = vec![];
+        for y0 in (0..self.height).step_by(tile_size as usize) {
+            for x0 in (0..self.width).step_by(tile_size as usize) {
+                tiles.push(TileBounds {
+                    x0,
+                    y0,
+                    x1: u32::min(x0 + tile_size, self.width),
+                    y1: u32::min(y0 + tile_size, self.height),
+                });
+            }
+        }
+        tiles
+    }
+
+    // A tile covering `bounds` grown by the filter radius, so samples near the
+    // tile edge can splat into pixels owned by neighbouring tiles.
+    pub fn tile(&self, bounds: TileBounds) -> FilmTile {
+        let r = self.filter.radius();
+        let grow = (r - 0.5).ceil().max(0.0) as u32;
+        let pixel_bounds = TileBounds {
+            x0: bounds.x0.saturating_sub(grow),
+            y0: bounds.y0.saturating_sub(grow),
+            x1: u32::min(bounds.x1 + grow, self.width),
+            y1: u32::min(bounds.y1 + grow, self.height),
+        };
+        let n = (pixel_bounds.x1 - pixel_bounds.x0) * (pixel_bounds.y1 - pixel_bounds.y0);
+        FilmTile {
+            sample_bounds: bounds,
+            pixel_bounds,
+            film_size: (self.width, self.height),
+            filter: self.filter.clone(),
+            pixels: vec![FilmPixel::default(); n as usize],
+        }
+    }
+
+    pub fn merge_tile(&mut self, tile: FilmTile) {
+        let b = tile.pixel_bounds;
+        let tile_width = b.x1 - b.x0;
+        for y in b.y0..b.y1 {
+            for x in b.x0..b.x1 {
+                let src = tile.pixels[((y - b.y0) * tile_width + (x - b.x0)) as usize];
+                let dst = &mut self.pixels[(y * self.width + x) as usize];
+                dst.weighted_sum += src.weighted_sum;
+                dst.weight_sum += src.weight_sum;
+                dst.albedo_sum += src.albedo_sum;
+                dst.normal_sum += src.normal_sum;
+                if dst.feature_samples == 0 {
+                    dst.object_id = src.object_id;
+                    dst.material_id = src.material_id;
+                }
+                dst.feature_samples += src.feature_samples;
+                dst.depth_sum += src.depth_sum;
+                dst.motion_sum += src.motion_sum;
+                dst.hit_samples += src.hit_samples;
+            }
+        }
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> FilmPixel {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    pub fn color_buffer(&self) -> Vec<Color> {
+        self.pixels.iter().map(FilmPixel::color).collect()
+    }
+
+    pub fn albedo_buffer(&self) -> Vec<Color> {
+        self.pixels.iter().map(FilmPixel::albedo).collect()
+    }
+
+    pub fn normal_buffer(&self) -> Vec<Vec3> {
+        self.pixels.iter().map(FilmPixel::normal).collect()
+    }
+
+    pub fn depth_buffer(&self) -> Vec<f64> {
+        self.pixels.iter().map(FilmPixel::depth).collect()
+    }
+
+    pub fn motion_buffer(&self) -> Vec<Vec3> {
+        self.pixels.iter().map(FilmPixel::motion).collect()
+    }
+
+    pub fn object_id_buffer(&self) -> Vec<u32> {
+        self.pixels.iter().map(|p| p.object_id).collect()
+    }
+
+    pub fn material_id_buffer(&self) -> Vec<u32> {
+        self.pixels.iter().map(|p| p.material_id).collect()
+    }
+
+    pub fn to_image(&self) -> RgbImage {
+        buffer_to_image(self.width, self.height, &self.color_buffer())
+    }
+}
+
+// Converts a row-major buffer of linear colours to an 8 bit image.
+pub fn buffer_to_image(width: u32, height: u32, buffer: &[Color]) -> RgbImage {
+    let mut img = RgbImage::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            img.put_pixel(x, y, to_rgb8(buffer[(y * width + x) as usize]));
+        }
+    }
+    img
+}
+
+pub struct FilmTile {
+    pub sample_bounds: TileBounds,
+    pub pixel_bounds: TileBounds,
+    pub film_size: (u32, u32),
+    filter: Arc<dyn Filter + Send + Sync>,
+    pixels: Vec<FilmPixel>,
+}
+
+impl FilmTile {
+    // `x` and `y` are continuous film coordinates; pixel (i, j) covers
+    // [i, i + 1) * [j, j + 1) and has its centre at (i + 0.5, j + 0.5).
+    pub fn add_sample(&mut self, x: f64, y: f64, color: Color) {
+        let r = self.filter.radius();
+        let b = self.pixel_bounds;
+        let x0 = f64::max((x - 0.5 - r).ceil(), b.x0 as f64) as u32;
+        let x1 = f64::min((x - 0.5 + r).floor() + 1.0, b.x1 as f64) as u32;
+        let y0 = f64::max((y - 0.5 - r).ceil(), b.y0 as f64) as u32;
+        let y1 = f64::min((y - 0.5 + r).floor() + 1.0, b.y1 as f64) as u32;
+        let tile_width = b.x1 - b.x0;
+
+        for py in y0..y1 {
+            for px in x0..x1 {
+                let weight = self
+                    .filter
+                    .evaluate(px as f64 + 0.5 - x, py as f64 + 0.5 - y);
+                if weight == 0.0 {
+                    continue;
+                }
+                let pixel = &mut self.pixels[((py - b.y0) * tile_width + (px - b.x0)) as usize];
+                pixel.weighted_sum += color * weight;
+                pixel.weight_sum += weight;
+            }
+        }
+    }
+
+    // Records first-hit features for the sample's own pixel (x, y).
+    pub fn add_features(&mut self, x: u32, y: u32, features: &SampleFeatures) {
+        let b = self.pixel_bounds;
+        let pixel = &mut self.pixels[((y - b.y0) * (b.x1 - b.x0) + (x - b.x0)) as usize];
+        if pixel.feature_samples == 0 {
+            pixel.object_id = features.object_id;
+            pixel.material_id = features.material_id;
+        }
+        pixel.albedo_sum += features.albedo;
+        pixel.normal_sum += features.normal;
+        pixel.feature_samples += 1;
+        if features.depth.is_finite() {
+            pixel.depth_sum += features.depth;
+            pixel.motion_sum += features.motion;
+            pixel.hit_samples += 1;
+        }
+    }
+}
+
+pub fn to_rgb8(color: Color) -> Rgb<u8> {
+    // gamma-correct for gamma =2.0
+    let r = color.x.max(0.0).sqrt();
+    let g = color.y.max(0.0).sqrt();
+    let b = color.z.max(0.0).sqrt();
+
+    // write the translated [0,255] value of each color component
+    let ir = (256.0 * clamp(r, 0.0, 0.999)) as u8;
+    let ig = (256.0 * clamp(g, 0.0, 0.999)) as u8;
+    let ib = (256.0 * clamp(b, 0.0, 0.999)) as u8;
+    Rgb([ir, ig, ib])
+}
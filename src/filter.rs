@@ -0,0 +1,164 @@
+use crate::rtweekend::PI;
+
+// Pixel reconstruction filter. `evaluate` takes the offset from a pixel centre
+// in pixels and is zero outside `radius` on either axis.
+pub trait Filter {
+    fn radius(&self) -> f64;
+    fn evaluate(&self, x: f64, y: f64) -> f64;
+}
+
+pub struct BoxFilter {
+    pub radius: f64,
+}
+
+impl BoxFilter {
+    pub fn new(radius: f64) -> BoxFilter {
+        BoxFilter { radius }
+    }
+}
+
+impl Filter for BoxFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        if x.abs() <= self.radius && y.abs() <= self.radius {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+pub struct TentFilter {
+    pub radius: f64,
+}
+
+impl TentFilter {
+    pub fn new(radius: f64) -> TentFilter {
+        TentFilter { radius }
+    }
+}
+
+impl Filter for TentFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        f64::max(0.0, self.radius - x.abs()) * f64::max(0.0, self.radius - y.abs())
+    }
+}
+
+pub struct GaussianFilter {
+    pub radius: f64,
+    pub sigma: f64,
+    exp_r: f64,
+}
+
+impl GaussianFilter {
+    pub fn new(radius: f64, sigma: f64) -> GaussianFilter {
+        GaussianFilter {
+            radius,
+            sigma,
+            exp_r: gaussian(radius, sigma),
+        }
+    }
+}
+
+impl Filter for GaussianFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        // Subtract the value at the radius so the filter falls to zero there.
+        f64::max(0.0, gaussian(x, self.sigma) - self.exp_r)
+            * f64::max(0.0, gaussian(y, self.sigma) - self.exp_r)
+    }
+}
+
+fn gaussian(x: f64, sigma: f64) -> f64 {
+    (-x * x / (2.0 * sigma * sigma)).exp()
+}
+
+// Mitchell-Netravali cubic. B = C = 1/3 is the authors' recommended trade-off
+// between blurring and ringing.
+pub struct MitchellFilter {
+    pub radius: f64,
+    pub b: f64,
+    pub c: f64,
+}
+
+impl MitchellFilter {
+    pub fn new(radius: f64, b: f64, c: f64) -> MitchellFilter {
+        MitchellFilter { radius, b, c }
+    }
+
+    fn mitchell_1d(&self, x: f64) -> f64 {
+        // The cubic is defined over [-2, 2]; scale the filter radius onto that.
+        let x = (2.0 * x / self.radius).abs();
+        let (b, c) = (self.b, self.c);
+        if x <= 1.0 {
+            ((12.0 - 9.0 * b - 6.0 * c) * x * x * x
+                + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                + (6.0 - 2.0 * b))
+                / 6.0
+        } else if x <= 2.0 {
+            ((-b - 6.0 * c) * x * x * x
+                + (6.0 * b + 30.0 * c) * x * x
+                + (-12.0 * b - 48.0 * c) * x
+                + (8.0 * b + 24.0 * c))
+                / 6.0
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Filter for MitchellFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        self.mitchell_1d(x) * self.mitchell_1d(y)
+    }
+}
+
+// Sinc windowed by a wider sinc; the window covers the whole radius, so
+// `LanczosFilter::new(3.0)` is the usual Lanczos-3.
+pub struct LanczosFilter {
+    pub radius: f64,
+}
+
+impl LanczosFilter {
+    pub fn new(radius: f64) -> LanczosFilter {
+        LanczosFilter { radius }
+    }
+
+    fn lanczos_1d(&self, x: f64) -> f64 {
+        if x.abs() > self.radius {
+            return 0.0;
+        }
+        sinc(x) * sinc(x / self.radius)
+    }
+}
+
+impl Filter for LanczosFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        self.lanczos_1d(x) * self.lanczos_1d(y)
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-5 {
+        return 1.0;
+    }
+    (PI * x).sin() / (PI * x)
+}
@@ -0,0 +1,392 @@
+use crate::{
+    hittable::HitRecord,
+    material::Material,
+    microfacet::fresnel_dielectric,
+    principled::luminance,
+    ray::Ray,
+    rtweekend::PI,
+    sampler::Sampler,
+    vec3::{cross, dot, Color, Vec3},
+};
+
+// Scattering orders modelled separately: reflection, transmission and one
+// internal reflection. Higher orders are lumped together.
+const P_MAX: usize = 3;
+
+// Pigments' absorption coefficients per unit concentration.
+const EUMELANIN: [f64; 3] = [0.419, 0.697, 1.37];
+const PHEOMELANIN: [f64; 3] = [0.187, 0.4, 1.05];
+
+// Hair fibre scattering after d'Eon et al. (2011) and Chiang et al. (2016): a
+// rough dielectric cylinder with an absorbing interior, as light reflects
+// (R), passes through (TT) or reflects inside once (TRT) or more. Each order
+// is a longitudinal lobe tilted by the cuticle scales times an azimuthal
+// logistic lobe. `beta_m` and `beta_n` are the longitudinal and azimuthal
+// roughness in [0, 1]. Meant for curves, whose v is taken as the offset across
+// the fibre and whose dp/du runs along it.
+pub struct Hair {
+    sigma_a: Color,
+    eta: f64,
+    beta_m: f64,
+    beta_n: f64,
+    alpha: f64,
+}
+
+impl Hair {
+    pub fn new(sigma_a: Color, beta_m: f64, beta_n: f64) -> Hair {
+        Hair {
+            sigma_a,
+            eta: 1.55,
+            beta_m,
+            beta_n,
+            alpha: 2.0,
+        }
+    }
+
+    // Hair coloured by its concentrations of eumelanin, from about 0.3 for
+    // blonde to 8 for black, and of red pheomelanin.
+    pub fn from_melanin(eumelanin: f64, pheomelanin: f64, beta_m: f64, beta_n: f64) -> Hair {
+        let sigma = |i: usize| eumelanin * EUMELANIN[i] + pheomelanin * PHEOMELANIN[i];
+        Hair::new(Color::new(sigma(0), sigma(1), sigma(2)), beta_m, beta_n)
+    }
+
+    // Hair whose multiply scattered colour is roughly `color`.
+    pub fn from_color(color: Color, beta_m: f64, beta_n: f64) -> Hair {
+        let k = reflectance_scale(beta_n);
+        let sigma = |c: f64| (c.max(1e-4).ln() / k).powi(2);
+        Hair::new(
+            Color::new(sigma(color.x), sigma(color.y), sigma(color.z)),
+            beta_m,
+            beta_n,
+        )
+    }
+
+    pub fn with_ior(mut self, eta: f64) -> Hair {
+        self.eta = eta;
+        self
+    }
+
+    // Tilt of the cuticle scales in degrees, 2 by default.
+    pub fn with_scale_angle(mut self, alpha: f64) -> Hair {
+        self.alpha = alpha;
+        self
+    }
+
+    fn lobes(&self, h: f64) -> Lobes {
+        let b = self.beta_m;
+        let v0 = (0.726 * b + 0.812 * b * b + 3.7 * b.powi(20)).powi(2);
+        let b = self.beta_n;
+        let s = (PI / 8.0).sqrt() * (0.265 * b + 1.194 * b * b + 5.372 * b.powi(22));
+        let mut sin_2k_alpha = [self.alpha.to_radians().sin(), 0.0, 0.0];
+        let mut cos_2k_alpha = [(1.0 - sin_2k_alpha[0].powi(2)).max(0.0).sqrt(), 0.0, 0.0];
+        for i in 1..3 {
+            sin_2k_alpha[i] = 2.0 * cos_2k_alpha[i - 1] * sin_2k_alpha[i - 1];
+            cos_2k_alpha[i] = cos_2k_alpha[i - 1].powi(2) - sin_2k_alpha[i - 1].powi(2);
+        }
+        Lobes {
+            h,
+            gamma_o: h.clamp(-1.0, 1.0).asin(),
+            v: [v0, 0.25 * v0, 4.0 * v0, 4.0 * v0],
+            s,
+            sin_2k_alpha,
+            cos_2k_alpha,
+        }
+    }
+
+    // Attenuation of each scattering order, and gamma_t, the offset angle of
+    // the refracted ray within the fibre.
+    fn attenuation(&self, lobes: &Lobes, sin_theta_o: f64) -> ([Color; P_MAX + 1], f64) {
+        let cos_theta_o = (1.0 - sin_theta_o * sin_theta_o).max(0.0).sqrt();
+        let sin_theta_t = sin_theta_o / self.eta;
+        let cos_theta_t = (1.0 - sin_theta_t * sin_theta_t).max(0.0).sqrt();
+        // The index of refraction seen in the plane across the fibre.
+        let etap = (self.eta * self.eta - sin_theta_o * sin_theta_o).sqrt() / cos_theta_o;
+        let sin_gamma_t = (lobes.h / etap).clamp(-1.0, 1.0);
+        let cos_gamma_t = (1.0 - sin_gamma_t * sin_gamma_t).sqrt();
+        let gamma_t = sin_gamma_t.asin();
+
+        let path = 2.0 * cos_gamma_t / cos_theta_t;
+        let t = Color::new(
+            (-self.sigma_a.x * path).exp(),
+            (-self.sigma_a.y * path).exp(),
+            (-self.sigma_a.z * path).exp(),
+        );
+        let cos_gamma_o = (1.0 - lobes.h * lobes.h).max(0.0).sqrt();
+        let f = fresnel_dielectric(cos_theta_o * cos_gamma_o, self.eta);
+        let mut ap = [Color::default(); P_MAX + 1];
+        ap[0] = Color::new(f, f, f);
+        ap[1] = t * (1.0 - f) * (1.0 - f);
+        for p in 2..P_MAX {
+            ap[p] = ap[p - 1] * t * f;
+        }
+        // The geometric series of all higher orders.
+        let tf = t * f;
+        let rest = ap[P_MAX - 1] * tf;
+        let series = |a: f64, tf: f64| a / (1.0 - tf).max(1e-6);
+        ap[P_MAX] = Color::new(
+            series(rest.x, tf.x),
+            series(rest.y, tf.y),
+            series(rest.z, tf.z),
+        );
+        (ap, gamma_t)
+    }
+
+    // Probability of sampling each scattering order.
+    fn order_pdf(ap: &[Color; P_MAX + 1]) -> [f64; P_MAX + 1] {
+        let total: f64 = ap.iter().map(|a| luminance(*a)).sum();
+        if total <= 0.0 {
+            return [1.0, 0.0, 0.0, 0.0];
+        }
+        ap.map(|a| luminance(a) / total)
+    }
+
+    // Sum of the lobes for local directions (x along the fibre), which is the
+    // BSDF times cos theta_i as the cosine is built into the lobes.
+    fn f(&self, lobes: &Lobes, wo: Vec3, wi: Vec3) -> Color {
+        let (sin_theta_o, cos_theta_o) = (wo.x, (1.0 - wo.x * wo.x).max(0.0).sqrt());
+        let (sin_theta_i, cos_theta_i) = (wi.x, (1.0 - wi.x * wi.x).max(0.0).sqrt());
+        let phi = wi.z.atan2(wi.y) - wo.z.atan2(wo.y);
+        let (ap, gamma_t) = self.attenuation(lobes, sin_theta_o);
+
+        let mut sum = Color::default();
+        for (p, a) in ap.iter().enumerate().take(P_MAX) {
+            let (sin_op, cos_op) = lobes.tilt(p, sin_theta_o, cos_theta_o);
+            sum += *a
+                * (mp(cos_theta_i, cos_op, sin_theta_i, sin_op, lobes.v[p])
+                    * np(phi, p, lobes.s, lobes.gamma_o, gamma_t));
+        }
+        sum + ap[P_MAX]
+            * (mp(
+                cos_theta_i,
+                cos_theta_o,
+                sin_theta_i,
+                sin_theta_o,
+                lobes.v[P_MAX],
+            ) / (2.0 * PI))
+    }
+
+    fn pdf(&self, lobes: &Lobes, wo: Vec3, wi: Vec3) -> f64 {
+        let (sin_theta_o, cos_theta_o) = (wo.x, (1.0 - wo.x * wo.x).max(0.0).sqrt());
+        let (sin_theta_i, cos_theta_i) = (wi.x, (1.0 - wi.x * wi.x).max(0.0).sqrt());
+        let phi = wi.z.atan2(wi.y) - wo.z.atan2(wo.y);
+        let (ap, gamma_t) = self.attenuation(lobes, sin_theta_o);
+        let order_pdf = Hair::order_pdf(&ap);
+
+        let mut pdf = 0.0;
+        for (p, weight) in order_pdf.iter().enumerate().take(P_MAX) {
+            let (sin_op, cos_op) = lobes.tilt(p, sin_theta_o, cos_theta_o);
+            pdf += weight
+                * mp(cos_theta_i, cos_op, sin_theta_i, sin_op, lobes.v[p])
+                * np(phi, p, lobes.s, lobes.gamma_o, gamma_t);
+        }
+        pdf + order_pdf[P_MAX]
+            * mp(
+                cos_theta_i,
+                cos_theta_o,
+                sin_theta_i,
+                sin_theta_o,
+                lobes.v[P_MAX],
+            )
+            / (2.0 * PI)
+    }
+
+    // Picks a scattering order, then theta_i from its longitudinal lobe and
+    // phi from its azimuthal one.
+    fn sample(&self, lobes: &Lobes, wo: Vec3, sampler: &mut dyn Sampler) -> Vec3 {
+        let (sin_theta_o, cos_theta_o) = (wo.x, (1.0 - wo.x * wo.x).max(0.0).sqrt());
+        let (ap, gamma_t) = self.attenuation(lobes, sin_theta_o);
+        let order_pdf = Hair::order_pdf(&ap);
+        let (mut u_order, u_phi) = sampler.get_2d();
+        let (u_theta, u_around) = sampler.get_2d();
+
+        let mut p = 0;
+        while p < P_MAX && u_order >= order_pdf[p] {
+            u_order -= order_pdf[p];
+            p += 1;
+        }
+        let (sin_op, cos_op) = if p < P_MAX {
+            lobes.tilt(p, sin_theta_o, cos_theta_o)
+        } else {
+            (sin_theta_o, cos_theta_o)
+        };
+
+        let v = lobes.v[p];
+        let u_theta = u_theta.max(1e-5);
+        let cos_theta = 1.0 + v * (u_theta + (1.0 - u_theta) * (-2.0 / v).exp()).ln();
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let cos_phi = (2.0 * PI * u_around).cos();
+        let sin_theta_i = -cos_theta * sin_op + sin_theta * cos_phi * cos_op;
+        let cos_theta_i = (1.0 - sin_theta_i * sin_theta_i).max(0.0).sqrt();
+
+        let dphi = if p < P_MAX {
+            phi_p(p, lobes.gamma_o, gamma_t) + sample_trimmed_logistic(u_phi, lobes.s, -PI, PI)
+        } else {
+            2.0 * PI * u_phi
+        };
+        let phi_i = wo.z.atan2(wo.y) + dphi;
+        Vec3::new(
+            sin_theta_i,
+            cos_theta_i * phi_i.cos(),
+            cos_theta_i * phi_i.sin(),
+        )
+    }
+}
+
+struct Lobes {
+    h: f64,
+    gamma_o: f64,
+    v: [f64; P_MAX + 1],
+    s: f64,
+    sin_2k_alpha: [f64; 3],
+    cos_2k_alpha: [f64; 3],
+}
+
+impl Lobes {
+    // theta_o rotated by the cuticle scales for scattering order p: -2 alpha
+    // for R, alpha for TT and 4 alpha for TRT.
+    fn tilt(&self, p: usize, sin_theta_o: f64, cos_theta_o: f64) -> (f64, f64) {
+        let (sin, cos) = match p {
+            0 => (-self.sin_2k_alpha[1], self.cos_2k_alpha[1]),
+            1 => (self.sin_2k_alpha[0], self.cos_2k_alpha[0]),
+            2 => (self.sin_2k_alpha[2], self.cos_2k_alpha[2]),
+            _ => (0.0, 1.0),
+        };
+        (
+            sin_theta_o * cos + cos_theta_o * sin,
+            (cos_theta_o * cos - sin_theta_o * sin).abs(),
+        )
+    }
+}
+
+// Local frame with x along the fibre and z towards the viewer, and the offset
+// h across the fibre.
+fn hair_frame(rec: &HitRecord) -> (Vec3, Vec3, Vec3, f64) {
+    let x = rec.dpdu.unit_vector();
+    let n = rec.geometric_normal;
+    let z = (n - x * dot(&n, &x)).unit_vector();
+    let y = cross(&z, &x);
+    (x, y, z, 1.0 - 2.0 * rec.v)
+}
+
+fn to_frame((x, y, z): (Vec3, Vec3, Vec3), w: Vec3) -> Vec3 {
+    Vec3::new(dot(&w, &x), dot(&w, &y), dot(&w, &z))
+}
+
+impl Material for Hair {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
+        let (x, y, z, h) = hair_frame(rec);
+        let lobes = self.lobes(h);
+        let wo = to_frame((x, y, z), -r_in.direction().unit_vector());
+        let wi = self.sample(&lobes, wo, sampler);
+        let pdf = self.pdf(&lobes, wo, wi);
+        if pdf <= 0.0 {
+            return false;
+        }
+        *attenuation = self.f(&lobes, wo, wi) / pdf;
+        *scattered = Ray::new(rec.p, x * wi.x + y * wi.y + z * wi.z, r_in.time());
+        true
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Option<Color> {
+        let (x, y, z, h) = hair_frame(rec);
+        let wo = to_frame((x, y, z), -r_in.direction().unit_vector());
+        let wi = to_frame((x, y, z), scattered.direction().unit_vector());
+        Some(self.f(&self.lobes(h), wo, wi))
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let (x, y, z, h) = hair_frame(rec);
+        let wo = to_frame((x, y, z), -r_in.direction().unit_vector());
+        let wi = to_frame((x, y, z), scattered.direction().unit_vector());
+        self.pdf(&self.lobes(h), wo, wi)
+    }
+
+    fn albedo(&self, _rec: &HitRecord) -> Color {
+        // The inverse of `from_color`.
+        let k = reflectance_scale(self.beta_n);
+        let c = |sigma: f64| (-sigma.sqrt() * k).exp();
+        Color::new(c(self.sigma_a.x), c(self.sigma_a.y), c(self.sigma_a.z))
+    }
+}
+
+// Fit by Chiang et al. relating absorption to the colour of a head of hair.
+fn reflectance_scale(beta_n: f64) -> f64 {
+    5.969 - 0.215 * beta_n + 2.532 * beta_n.powi(2) - 10.73 * beta_n.powi(3)
+        + 5.574 * beta_n.powi(4)
+        + 0.245 * beta_n.powi(5)
+}
+
+// Longitudinal scattering lobe with variance v.
+fn mp(cos_theta_i: f64, cos_theta_o: f64, sin_theta_i: f64, sin_theta_o: f64, v: f64) -> f64 {
+    let a = cos_theta_i * cos_theta_o / v;
+    let b = sin_theta_i * sin_theta_o / v;
+    if v <= 0.1 {
+        // In log space, where narrow lobes would otherwise overflow.
+        (log_i0(a) - b - 1.0 / v + std::f64::consts::LN_2 + (1.0 / (2.0 * v)).ln()).exp()
+    } else {
+        ((-b).exp() * i0(a)) / ((1.0 / v).sinh() * 2.0 * v)
+    }
+}
+
+// Modified Bessel function of the first kind and order zero.
+fn i0(x: f64) -> f64 {
+    let mut sum = 0.0;
+    let mut x2i = 1.0;
+    let mut factorial = 1.0;
+    let mut four_i = 1.0;
+    for i in 0..10 {
+        if i > 1 {
+            factorial *= i as f64;
+        }
+        sum += x2i / (four_i * factorial * factorial);
+        x2i *= x * x;
+        four_i *= 4.0;
+    }
+    sum
+}
+
+fn log_i0(x: f64) -> f64 {
+    if x > 12.0 {
+        x + 0.5 * (-(2.0 * PI).ln() + (1.0 / x).ln() + 1.0 / (8.0 * x))
+    } else {
+        i0(x).ln()
+    }
+}
+
+// Azimuth of light leaving after scattering order p.
+fn phi_p(p: usize, gamma_o: f64, gamma_t: f64) -> f64 {
+    2.0 * p as f64 * gamma_t - 2.0 * gamma_o + p as f64 * PI
+}
+
+// Azimuthal lobe for scattering order p.
+fn np(phi: f64, p: usize, s: f64, gamma_o: f64, gamma_t: f64) -> f64 {
+    let mut dphi = phi - phi_p(p, gamma_o, gamma_t);
+    dphi = (dphi + PI).rem_euclid(2.0 * PI) - PI;
+    trimmed_logistic(dphi, s, -PI, PI)
+}
+
+fn logistic(x: f64, s: f64) -> f64 {
+    let e = (-x.abs() / s).exp();
+    e / (s * (1.0 + e) * (1.0 + e))
+}
+
+fn logistic_cdf(x: f64, s: f64) -> f64 {
+    1.0 / (1.0 + (-x / s).exp())
+}
+
+fn trimmed_logistic(x: f64, s: f64, a: f64, b: f64) -> f64 {
+    logistic(x, s) / (logistic_cdf(b, s) - logistic_cdf(a, s))
+}
+
+fn sample_trimmed_logistic(u: f64, s: f64, a: f64, b: f64) -> f64 {
+    let k = logistic_cdf(b, s) - logistic_cdf(a, s);
+    let x = -s * (1.0 / (u * k + logistic_cdf(a, s)) - 1.0).ln();
+    x.clamp(a, b)
+}
@@ -0,0 +1,328 @@
+use std::sync::Arc;
+
+use image::{
+    error::{ParameterError, ParameterErrorKind},
+    DynamicImage, ImageError, ImageResult,
+};
+
+use crate::{
+    aabb::Aabb, hittable::*, material::Material, ray::Ray, triangle::intersect_triangle, vec3::*,
+};
+
+// Height range over blocks of 2^level by 2^level cells.
+struct Level {
+    width: usize,
+    depth: usize,
+    ranges: Vec<(f32, f32)>,
+}
+
+// Terrain from a grid of heights in [0, 1], spread over `size.x` by `size.z`
+// from `corner` and scaled by `size.y`. Each cell is two triangles shaded with
+// normals interpolated from the slopes of the grid, which costs a height per
+// sample rather than a mesh. Rays descend a quadtree of height ranges over
+// blocks of cells, a min/max mipmap, nearest block first, skipping blocks
+// they pass over or under. u runs along x and v against z, so an image draped
+// with `ImageTexture` lines up with the heightmap it came from.
+pub struct Heightfield {
+    heights: Vec<f32>,
+    width: usize,
+    depth: usize,
+    corner: Point3,
+    size: Vec3,
+    // Finest first, down to a single block.
+    levels: Vec<Level>,
+    mat_ptr: Arc<dyn Material + Sync + Send>,
+    id: u32,
+    material_id: u32,
+}
+
+// A hit on one of the triangles of a cell.
+#[derive(Clone, Copy)]
+struct CellHit {
+    t: f64,
+    i: usize,
+    j: usize,
+    // Position within the cell, in cells along x and z.
+    x: f64,
+    z: f64,
+    // Weights of the cell's three corners on the triangle hit.
+    corners: [(usize, usize, f64); 3],
+}
+
+impl Heightfield {
+    // `heights` holds `depth` rows of `width` samples along x, rows running
+    // along z.
+    pub fn new(
+        heights: Vec<f32>,
+        width: usize,
+        depth: usize,
+        corner: Point3,
+        size: Vec3,
+        mat_ptr: Arc<dyn Material + Sync + Send>,
+    ) -> Heightfield {
+        assert!(
+            width >= 2 && depth >= 2,
+            "a heightfield needs 2 by 2 samples"
+        );
+        assert_eq!(heights.len(), width * depth, "wrong number of heights");
+
+        let at = |i: usize, j: usize| heights[j * width + i];
+        let mut level = Level {
+            width: width - 1,
+            depth: depth - 1,
+            ranges: Vec::with_capacity((width - 1) * (depth - 1)),
+        };
+        for j in 0..depth - 1 {
+            for i in 0..width - 1 {
+                let h = [at(i, j), at(i + 1, j), at(i, j + 1), at(i + 1, j + 1)];
+                let lo = h.iter().cloned().fold(f32::INFINITY, f32::min);
+                let hi = h.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+                level.ranges.push((lo, hi));
+            }
+        }
+        let mut levels = vec![level];
+        while let Some(fine) = levels.last().filter(|l| l.width > 1 || l.depth > 1) {
+            let (w, d) = (fine.width.div_ceil(2), fine.depth.div_ceil(2));
+            let mut ranges = vec![(f32::INFINITY, f32::NEG_INFINITY); w * d];
+            for j in 0..fine.depth {
+                for i in 0..fine.width {
+                    let (lo, hi) = fine.ranges[j * fine.width + i];
+                    let range = &mut ranges[(j / 2) * w + i / 2];
+                    *range = (range.0.min(lo), range.1.max(hi));
+                }
+            }
+            levels.push(Level {
+                width: w,
+                depth: d,
+                ranges,
+            });
+        }
+
+        Heightfield {
+            heights,
+            width,
+            depth,
+            corner,
+            size,
+            levels,
+            id: new_object_id(),
+            material_id: material_id(&mat_ptr),
+            mat_ptr,
+        }
+    }
+
+    // Heights from the brightness of an image, its top row at `corner.z`.
+    // Images narrower or shorter than 2 pixels are an error.
+    pub fn from_image(
+        img: &DynamicImage,
+        corner: Point3,
+        size: Vec3,
+        mat_ptr: Arc<dyn Material + Sync + Send>,
+    ) -> ImageResult<Heightfield> {
+        let img = img.to_luma16();
+        let (width, depth) = img.dimensions();
+        if width < 2 || depth < 2 {
+            return Err(ImageError::Parameter(ParameterError::from_kind(
+                ParameterErrorKind::Generic("a heightfield needs 2 by 2 samples".to_string()),
+            )));
+        }
+        let heights = img.pixels().map(|p| p[0] as f32 / 65535.0).collect();
+        Ok(Heightfield::new(
+            heights,
+            width as usize,
+            depth as usize,
+            corner,
+            size,
+            mat_ptr,
+        ))
+    }
+
+    pub fn load(
+        path: &str,
+        corner: Point3,
+        size: Vec3,
+        mat_ptr: Arc<dyn Material + Sync + Send>,
+    ) -> ImageResult<Heightfield> {
+        Heightfield::from_image(&image::open(path)?, corner, size, mat_ptr)
+    }
+
+    fn spacing(&self) -> (f64, f64) {
+        (
+            self.size.x / (self.width - 1) as f64,
+            self.size.z / (self.depth - 1) as f64,
+        )
+    }
+
+    fn height(&self, i: usize, j: usize) -> f64 {
+        self.heights[j * self.width + i] as f64
+    }
+
+    fn point(&self, i: usize, j: usize) -> Point3 {
+        let (dx, dz) = self.spacing();
+        self.corner
+            + Vec3::new(
+                i as f64 * dx,
+                self.height(i, j) * self.size.y,
+                j as f64 * dz,
+            )
+    }
+
+    // Smooth normal at a sample, from central differences of the heights.
+    fn normal(&self, i: usize, j: usize) -> Vec3 {
+        let (dx, dz) = self.spacing();
+        let (i0, i1) = (i.saturating_sub(1), (i + 1).min(self.width - 1));
+        let (j0, j1) = (j.saturating_sub(1), (j + 1).min(self.depth - 1));
+        let slope_x =
+            (self.height(i1, j) - self.height(i0, j)) * self.size.y / ((i1 - i0) as f64 * dx);
+        let slope_z =
+            (self.height(i, j1) - self.height(i, j0)) * self.size.y / ((j1 - j0) as f64 * dz);
+        Vec3::new(-slope_x, 1.0, -slope_z).unit_vector()
+    }
+
+    // Box around the cells of a block, padded so flat ones have some height.
+    fn block_box(&self, level: usize, i: usize, j: usize) -> Aabb {
+        let (dx, dz) = self.spacing();
+        let cells = 1 << level;
+        let (lo, hi) = self.levels[level].ranges[j * self.levels[level].width + i];
+        let x0 = i * cells;
+        let z0 = j * cells;
+        let x1 = ((i + 1) * cells).min(self.width - 1);
+        let z1 = ((j + 1) * cells).min(self.depth - 1);
+        let pad = 1e-6 * (self.size.x.abs() + self.size.y.abs() + self.size.z.abs());
+        let a = self.corner + Vec3::new(x0 as f64 * dx, lo as f64 * self.size.y, z0 as f64 * dz);
+        let b = self.corner + Vec3::new(x1 as f64 * dx, hi as f64 * self.size.y, z1 as f64 * dz);
+        let pad = Vec3::new(pad, pad, pad);
+        Aabb::new(
+            Point3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)) - pad,
+            Point3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z)) + pad,
+        )
+    }
+
+    // Nearest hit within a block, closer than the hit found so far.
+    fn search(
+        &self,
+        r: &Ray,
+        (level, i, j): (usize, usize, usize),
+        t_min: f64,
+        t_max: f64,
+        closest: &mut Option<CellHit>,
+    ) {
+        if level == 0 {
+            self.hit_cell(r, i, j, t_min, t_max, closest);
+            return;
+        }
+        let fine = &self.levels[level - 1];
+        let mut children = [(f64::INFINITY, 0, 0); 4];
+        let mut count = 0;
+        for (ci, cj) in [
+            (2 * i, 2 * j),
+            (2 * i + 1, 2 * j),
+            (2 * i, 2 * j + 1),
+            (2 * i + 1, 2 * j + 1),
+        ] {
+            if ci >= fine.width || cj >= fine.depth {
+                continue;
+            }
+            let t_far = closest.map_or(t_max, |h| h.t);
+            if let Some((t0, _)) = self.block_box(level - 1, ci, cj).clip(r, t_min, t_far) {
+                children[count] = (t0, ci, cj);
+                count += 1;
+            }
+        }
+        let children = &mut children[..count];
+        children.sort_by(|a, b| a.0.total_cmp(&b.0));
+        for &(t0, ci, cj) in children.iter() {
+            if closest.is_some_and(|h| h.t < t0) {
+                break;
+            }
+            self.search(r, (level - 1, ci, cj), t_min, t_max, closest);
+        }
+    }
+
+    // The cell's two triangles, split along the diagonal from (i, j).
+    fn hit_cell(
+        &self,
+        r: &Ray,
+        i: usize,
+        j: usize,
+        t_min: f64,
+        t_max: f64,
+        closest: &mut Option<CellHit>,
+    ) {
+        let triangles = [
+            [(i, j), (i + 1, j), (i + 1, j + 1)],
+            [(i, j), (i + 1, j + 1), (i, j + 1)],
+        ];
+        for [a, b, c] in triangles {
+            let t_far = closest.map_or(t_max, |h| h.t);
+            let vertices = [
+                self.point(a.0, a.1),
+                self.point(b.0, b.1),
+                self.point(c.0, c.1),
+            ];
+            if let Some((t, b1, b2)) = intersect_triangle(r, vertices, t_min, t_far) {
+                let b0 = 1.0 - b1 - b2;
+                let local = |k: (usize, usize)| ((k.0 - i) as f64, (k.1 - j) as f64);
+                let (la, lb, lc) = (local(a), local(b), local(c));
+                *closest = Some(CellHit {
+                    t,
+                    i,
+                    j,
+                    x: b0 * la.0 + b1 * lb.0 + b2 * lc.0,
+                    z: b0 * la.1 + b1 * lb.1 + b2 * lc.1,
+                    corners: [(a.0, a.1, b0), (b.0, b.1, b1), (c.0, c.1, b2)],
+                });
+            }
+        }
+    }
+}
+
+impl Hittable for Heightfield {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let top = self.levels.len() - 1;
+        if self.block_box(top, 0, 0).clip(r, t_min, t_max).is_none() {
+            return false;
+        }
+        let mut closest = None;
+        self.search(r, (top, 0, 0), t_min, t_max, &mut closest);
+        let hit = match closest {
+            Some(hit) => hit,
+            None => return false,
+        };
+
+        rec.t = hit.t;
+        rec.p = r.at(hit.t);
+        let [a, b, c] = hit.corners.map(|(i, j, _)| self.point(i, j));
+        let mut outward_normal = cross(&(b - a), &(c - a)).unit_vector();
+        if outward_normal.y * self.size.y < 0.0 {
+            outward_normal = -outward_normal;
+        }
+        rec.set_face_normal(r, &outward_normal);
+        let mut normal = Vec3::default();
+        for (i, j, w) in hit.corners {
+            normal += self.normal(i, j) * w;
+        }
+        let normal = normal.unit_vector();
+        rec.set_shading_normal(normal);
+
+        let fx = hit.i as f64 + hit.x;
+        let fz = hit.j as f64 + hit.z;
+        rec.u = fx / (self.width - 1) as f64;
+        rec.v = 1.0 - fz / (self.depth - 1) as f64;
+        // Along the tangent plane of the smooth normal.
+        let slope_x = -normal.x / normal.y;
+        let slope_z = -normal.z / normal.y;
+        rec.dpdu = Vec3::new(1.0, slope_x, 0.0) * self.size.x;
+        rec.dpdv = Vec3::new(0.0, slope_z, 1.0) * -self.size.z;
+        rec.mat_ptr = self.mat_ptr.clone();
+        rec.object_id = self.id;
+        rec.material_id = self.material_id;
+        rec.velocity = Vec3::default();
+        true
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64, output_box: &mut Aabb) -> bool {
+        *output_box = self.block_box(self.levels.len() - 1, 0, 0);
+        true
+    }
+}
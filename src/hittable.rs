@@ -1,4 +1,7 @@
-use std::sync::Arc;
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc, Mutex,
+};
 
 use crate::{
     aabb::Aabb,
@@ -10,10 +13,31 @@ use crate::{
 #[derive(Clone)]
 pub struct HitRecord {
     pub p: Point3,
+    // Shading normal, which materials use. It starts out as the geometric
+    // normal and may be perturbed by normal or bump mapping.
     pub normal: Vec3,
+    // True surface normal, facing the incoming ray like `normal`.
+    pub geometric_normal: Vec3,
+    // Partial derivatives of the surface point with respect to u and v.
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
     pub mat_ptr: Arc<dyn Material + Sync + Send>,
     pub t: f64,
+    pub u: f64,
+    pub v: f64,
     pub front_face: bool,
+    // Identify the primitive that was hit and its material, for the ID passes
+    // and for telling media apart.
+    pub object_id: u32,
+    pub material_id: u32,
+    // Motion of the surface point per unit time, for the motion vector pass.
+    pub velocity: Vec3,
+    // Colour interpolated from the vertices of a mesh that has them. Only
+    // meshes set it; containers clear it for everything else.
+    pub vertex_color: Option<Color>,
+    // Index of refraction on the outer side of the surface, filled in by the
+    // integrator when the hit is on the boundary of a medium.
+    pub outside_ior: f64,
 }
 
 impl Default for HitRecord {
@@ -21,9 +45,19 @@ impl Default for HitRecord {
         HitRecord {
             p: Point3::default(),
             normal: Vec3::default(),
+            geometric_normal: Vec3::default(),
+            dpdu: Vec3::default(),
+            dpdv: Vec3::default(),
             t: 0.0,
+            u: 0.0,
+            v: 0.0,
             front_face: true,
             mat_ptr: Arc::new(Lambertian::new(Color::default())),
+            object_id: 0,
+            material_id: 0,
+            velocity: Vec3::default(),
+            vertex_color: None,
+            outside_ior: 1.0,
         }
     }
 }
@@ -35,11 +69,73 @@ impl HitRecord {
             *outward_normal
         } else {
             -*outward_normal
+        };
+        self.geometric_normal = self.normal;
+    }
+
+    // Replaces the shading normal, keeping it on the same side of the surface
+    // as the geometric normal.
+    pub fn set_shading_normal(&mut self, n: Vec3) {
+        self.normal = if dot(&n, &self.geometric_normal) < 0.0 {
+            -n
+        } else {
+            n
+        };
+    }
+}
+
+// Ids are numbered in the order objects are built, so a scene built the same
+// way gets the same ids on every run. Zero is left for "nothing hit".
+static NEXT_OBJECT_ID: AtomicU32 = AtomicU32::new(1);
+static MATERIALS: Mutex<Vec<Arc<dyn Material + Sync + Send>>> = Mutex::new(Vec::new());
+
+// Reserves `count` consecutive object ids and returns the first.
+pub fn new_object_ids(count: usize) -> u32 {
+    NEXT_OBJECT_ID.fetch_add(count as u32, Ordering::Relaxed)
+}
+
+pub fn new_object_id() -> u32 {
+    new_object_ids(1)
+}
+
+// Id of a material, numbered in the order materials are first given to
+// objects. Materials are kept once numbered, so another can't take the
+// address of one that was dropped.
+pub fn material_id(mat_ptr: &Arc<dyn Material + Sync + Send>) -> u32 {
+    let mut materials = MATERIALS.lock().unwrap();
+    let address = |m: &Arc<dyn Material + Sync + Send>| Arc::as_ptr(m) as *const u8;
+    match materials
+        .iter()
+        .position(|m| address(m) == address(mat_ptr))
+    {
+        Some(i) => i as u32 + 1,
+        None => {
+            materials.push(mat_ptr.clone());
+            materials.len() as u32
         }
     }
 }
 
+// Crossings `hit_all` gives up after, in case a surface keeps being hit at the
+// same distance.
+const MAX_CROSSINGS: usize = 64;
+
 pub trait Hittable {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool;
     fn bounding_box(&self, time0: f64, time1: f64, output_box: &mut Aabb) -> bool;
+
+    // Every crossing of the surface along the ray within [t_min, t_max], in
+    // order, for constructive solid geometry. `front_face` tells whether each
+    // crossing enters or leaves the solid. The default steps from one hit to
+    // the next.
+    fn hit_all(&self, r: &Ray, t_min: f64, t_max: f64) -> Vec<HitRecord> {
+        let mut hits = Vec::new();
+        let mut t = t_min;
+        let mut rec = HitRecord::default();
+        while hits.len() < MAX_CROSSINGS && self.hit(r, t, t_max, &mut rec) {
+            t = rec.t + 1e-9 * (1.0 + rec.t.abs());
+            hits.push(rec.clone());
+        }
+        hits
+    }
 }
@@ -0,0 +1,154 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb, hittable::*, interval::Interval, material::Material, onb::Onb, ray::Ray,
+    sphere::get_sphere_uv, vec3::*,
+};
+
+// A function whose zero set is a surface, able to bound its own values over
+// a box with interval arithmetic.
+pub trait ImplicitFunction {
+    fn value(&self, p: Point3) -> f64;
+
+    // An interval containing every value over the box x by y by z.
+    fn bounds(&self, x: Interval, y: Interval, z: Interval) -> Interval;
+
+    // Points away from the surface on the positive side. The default uses
+    // central differences.
+    fn gradient(&self, p: Point3) -> Vec3 {
+        const H: f64 = 1e-6;
+        let dx = Vec3::new(H, 0.0, 0.0);
+        let dy = Vec3::new(0.0, H, 0.0);
+        let dz = Vec3::new(0.0, 0.0, H);
+        Vec3::new(
+            self.value(p + dx) - self.value(p - dx),
+            self.value(p + dy) - self.value(p - dy),
+            self.value(p + dz) - self.value(p - dz),
+        ) / (2.0 * H)
+    }
+}
+
+// Surface where an implicit function is zero, inside `bbox`, with the
+// positive side outside. The ray is split in halves, nearest first, as long
+// as the interval bounds of the function over a piece include zero; a piece
+// whose bounds exclude zero can't hold any of the surface and is skipped. The
+// first piece shorter than `tolerance` over which the function changes sign
+// is taken as the hit, so features thinner than the tolerance may be missed
+// but thicker ones never are, unlike with fixed steps along the ray.
+pub struct Implicit {
+    f: Arc<dyn ImplicitFunction + Send + Sync>,
+    bbox: Aabb,
+    mat_ptr: Arc<dyn Material + Sync + Send>,
+    id: u32,
+    material_id: u32,
+    tolerance: f64,
+}
+
+// Splits of the ray beyond which a piece is taken as a hit, whatever its
+// length.
+const MAX_DEPTH: u32 = 48;
+
+impl Implicit {
+    pub fn new(
+        f: Arc<dyn ImplicitFunction + Send + Sync>,
+        bbox: Aabb,
+        mat_ptr: Arc<dyn Material + Sync + Send>,
+    ) -> Implicit {
+        Implicit {
+            f,
+            bbox,
+            id: new_object_id(),
+            material_id: material_id(&mat_ptr),
+            mat_ptr,
+            tolerance: 1e-4,
+        }
+    }
+
+    pub fn with_tolerance(mut self, tolerance: f64) -> Implicit {
+        self.tolerance = tolerance;
+        self
+    }
+
+    // First root of the function along o + t d between `start` and `end`,
+    // each a value of t and the function there.
+    fn first_root(
+        &self,
+        o: Point3,
+        d: Vec3,
+        start: (f64, f64),
+        end: (f64, f64),
+        depth: u32,
+    ) -> Option<f64> {
+        let ((t0, f0), (t1, f1)) = (start, end);
+        let span = Interval::new(t0, t1);
+        let bounds = self
+            .f
+            .bounds(span * d.x + o.x, span * d.y + o.y, span * d.z + o.z);
+        if !bounds.contains(0.0) {
+            return None;
+        }
+        if (t1 - t0) * d.length() < self.tolerance || depth == MAX_DEPTH {
+            return if f0 * f1 <= 0.0 {
+                Some(refine(|t| self.f.value(o + d * t), t0, t1, f0))
+            } else {
+                None
+            };
+        }
+        let mid = 0.5 * (t0 + t1);
+        let f_mid = self.f.value(o + d * mid);
+        self.first_root(o, d, start, (mid, f_mid), depth + 1)
+            .or_else(|| self.first_root(o, d, (mid, f_mid), end, depth + 1))
+    }
+}
+
+impl Hittable for Implicit {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let (t0, t1) = match self.bbox.clip(r, t_min, t_max) {
+            Some(range) => range,
+            None => return false,
+        };
+        let (o, d) = (*r.origin(), *r.direction());
+        let start = (t0, self.f.value(o + d * t0));
+        let end = (t1, self.f.value(o + d * t1));
+        let t = match self.first_root(o, d, start, end, 0) {
+            Some(t) => t,
+            None => return false,
+        };
+
+        rec.t = t;
+        rec.p = r.at(t);
+        let outward_normal = self.f.gradient(rec.p);
+        if outward_normal.near_zero() {
+            return false;
+        }
+        let outward_normal = outward_normal.unit_vector();
+        let frame = Onb::build_from_w(&outward_normal);
+        rec.set_face_normal(r, &outward_normal);
+        (rec.u, rec.v) = get_sphere_uv(&outward_normal);
+        (rec.dpdu, rec.dpdv) = (frame.u, frame.v);
+        rec.mat_ptr = self.mat_ptr.clone();
+        rec.object_id = self.id;
+        rec.material_id = self.material_id;
+        rec.velocity = Vec3::default();
+        true
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64, output_box: &mut Aabb) -> bool {
+        *output_box = self.bbox;
+        true
+    }
+}
+
+// Bisection of a sign change of g over [a, b], where g(a) = ga.
+fn refine(g: impl Fn(f64) -> f64, mut a: f64, mut b: f64, ga: f64) -> f64 {
+    for _ in 0..32 {
+        let mid = 0.5 * (a + b);
+        let g_mid = g(mid);
+        if (g_mid < 0.0) == (ga < 0.0) && g_mid != 0.0 {
+            a = mid;
+        } else {
+            b = mid;
+        }
+    }
+    0.5 * (a + b)
+}
@@ -0,0 +1,140 @@
+use std::ops::{Add, Mul, Neg, Sub};
+
+// Closed range of reals for interval arithmetic: the result of an operation on
+// intervals contains the result of the operation on any values taken from
+// them, so a function evaluated on intervals bounds its values over a box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interval {
+    pub lo: f64,
+    pub hi: f64,
+}
+
+impl Interval {
+    pub fn new(a: f64, b: f64) -> Interval {
+        Interval {
+            lo: a.min(b),
+            hi: a.max(b),
+        }
+    }
+
+    pub fn point(x: f64) -> Interval {
+        Interval { lo: x, hi: x }
+    }
+
+    pub fn contains(&self, x: f64) -> bool {
+        self.lo <= x && x <= self.hi
+    }
+
+    pub fn width(&self) -> f64 {
+        self.hi - self.lo
+    }
+
+    // Tighter than `self * self`, which can't know both factors are the same.
+    pub fn sqr(self) -> Interval {
+        let (a, b) = (self.lo * self.lo, self.hi * self.hi);
+        if self.contains(0.0) {
+            Interval::new(0.0, a.max(b))
+        } else {
+            Interval::new(a, b)
+        }
+    }
+
+    pub fn powi(self, n: i32) -> Interval {
+        if n % 2 == 0 {
+            let (a, b) = (self.lo.abs().powi(n), self.hi.abs().powi(n));
+            if self.contains(0.0) {
+                Interval::new(0.0, a.max(b))
+            } else {
+                Interval::new(a, b)
+            }
+        } else {
+            Interval::new(self.lo.powi(n), self.hi.powi(n))
+        }
+    }
+
+    // Square root of the non-negative part.
+    pub fn sqrt(self) -> Interval {
+        Interval::new(self.lo.max(0.0).sqrt(), self.hi.max(0.0).sqrt())
+    }
+
+    pub fn abs(self) -> Interval {
+        if self.contains(0.0) {
+            Interval::new(0.0, self.lo.abs().max(self.hi))
+        } else {
+            Interval::new(self.lo.abs(), self.hi.abs())
+        }
+    }
+
+    pub fn min(self, other: Interval) -> Interval {
+        Interval::new(self.lo.min(other.lo), self.hi.min(other.hi))
+    }
+
+    pub fn max(self, other: Interval) -> Interval {
+        Interval::new(self.lo.max(other.lo), self.hi.max(other.hi))
+    }
+}
+
+impl Add for Interval {
+    type Output = Interval;
+
+    fn add(self, rhs: Interval) -> Interval {
+        Interval::new(self.lo + rhs.lo, self.hi + rhs.hi)
+    }
+}
+
+impl Sub for Interval {
+    type Output = Interval;
+
+    fn sub(self, rhs: Interval) -> Interval {
+        Interval::new(self.lo - rhs.hi, self.hi - rhs.lo)
+    }
+}
+
+impl Mul for Interval {
+    type Output = Interval;
+
+    fn mul(self, rhs: Interval) -> Interval {
+        let p = [
+            self.lo * rhs.lo,
+            self.lo * rhs.hi,
+            self.hi * rhs.lo,
+            self.hi * rhs.hi,
+        ];
+        Interval {
+            lo: p.iter().cloned().fold(f64::INFINITY, f64::min),
+            hi: p.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+        }
+    }
+}
+
+impl Neg for Interval {
+    type Output = Interval;
+
+    fn neg(self) -> Interval {
+        Interval::new(-self.hi, -self.lo)
+    }
+}
+
+impl Add<f64> for Interval {
+    type Output = Interval;
+
+    fn add(self, rhs: f64) -> Interval {
+        Interval::new(self.lo + rhs, self.hi + rhs)
+    }
+}
+
+impl Sub<f64> for Interval {
+    type Output = Interval;
+
+    fn sub(self, rhs: f64) -> Interval {
+        Interval::new(self.lo - rhs, self.hi - rhs)
+    }
+}
+
+impl Mul<f64> for Interval {
+    type Output = Interval;
+
+    fn mul(self, rhs: f64) -> Interval {
+        Interval::new(self.lo * rhs, self.hi * rhs)
+    }
+}
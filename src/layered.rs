@@ -0,0 +1,260 @@
+use std::sync::Arc;
+
+use crate::{
+    hittable::HitRecord,
+    material::Material,
+    medium::Medium,
+    microfacet::fresnel_dielectric,
+    ray::Ray,
+    sampler::Sampler,
+    texture::{SolidColor, Texture},
+    vec3::{dot, reflect, refract, Color},
+};
+
+// Blend of two materials, `b` weighted by the scalar value of `mask` and `a` by
+// the rest, e.g. rust over metal or dust over plastic. One of the two is
+// picked per scatter; when both can be evaluated the result is weighted with
+// the blended pdf, otherwise with the pdf of the material that was picked.
+pub struct MixMaterial {
+    pub a: Arc<dyn Material + Send + Sync>,
+    pub b: Arc<dyn Material + Send + Sync>,
+    pub mask: Arc<dyn Texture + Send + Sync>,
+}
+
+impl MixMaterial {
+    pub fn new(
+        a: Arc<dyn Material + Send + Sync>,
+        b: Arc<dyn Material + Send + Sync>,
+        mask: Arc<dyn Texture + Send + Sync>,
+    ) -> MixMaterial {
+        MixMaterial { a, b, mask }
+    }
+
+    // Constant blend weight for `b`.
+    pub fn uniform(
+        a: Arc<dyn Material + Send + Sync>,
+        b: Arc<dyn Material + Send + Sync>,
+        weight: f64,
+    ) -> MixMaterial {
+        MixMaterial::new(a, b, SolidColor::uniform(weight))
+    }
+
+    fn weight(&self, rec: &HitRecord) -> f64 {
+        self.mask.scalar(rec.u, rec.v, &rec.p).clamp(0.0, 1.0)
+    }
+}
+
+impl Material for MixMaterial {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
+        let w = self.weight(rec);
+        let chosen = if sampler.get_1d() < w {
+            &self.b
+        } else {
+            &self.a
+        };
+        if !chosen.scatter(r_in, rec, attenuation, scattered, sampler) {
+            return false;
+        }
+        if let Some(f) = self.eval(r_in, rec, scattered) {
+            let pdf = self.scattering_pdf(r_in, rec, scattered);
+            if pdf > 0.0 {
+                *attenuation = f / pdf;
+            }
+        }
+        true
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Option<Color> {
+        let w = self.weight(rec);
+        let fa = self.a.eval(r_in, rec, scattered)?;
+        let fb = self.b.eval(r_in, rec, scattered)?;
+        Some(fa * (1.0 - w) + fb * w)
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let w = self.weight(rec);
+        self.a.scattering_pdf(r_in, rec, scattered) * (1.0 - w)
+            + self.b.scattering_pdf(r_in, rec, scattered) * w
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        let w = self.weight(rec);
+        self.a.albedo(rec) * (1.0 - w) + self.b.albedo(rec) * w
+    }
+
+    fn medium(&self, wavelength: Option<f64>) -> Option<Medium> {
+        self.a
+            .medium(wavelength)
+            .or_else(|| self.b.medium(wavelength))
+    }
+
+    fn dispersive(&self) -> bool {
+        self.a.dispersive() || self.b.dispersive()
+    }
+}
+
+// Smooth dielectric coating, such as varnish or lacquer, over any material.
+// Light is reflected by the coating or refracted into it, bounces between the
+// base and the underside of the coating until it escapes, and is tinted by
+// `tint` each time it crosses the coating, scaled by the length of the
+// crossing relative to a perpendicular one.
+pub struct Coated {
+    pub base: Arc<dyn Material + Send + Sync>,
+    pub ior: f64,
+    pub tint: Color,
+}
+
+// Bounces inside the coating after which the path is given up.
+const MAX_COATING_BOUNCES: u32 = 16;
+
+impl Coated {
+    pub fn new(base: Arc<dyn Material + Send + Sync>, ior: f64) -> Coated {
+        Coated {
+            base,
+            ior,
+            tint: Color::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    pub fn with_tint(mut self, tint: Color) -> Coated {
+        self.tint = tint;
+        self
+    }
+
+    fn transmittance(&self, cos_theta: f64) -> Color {
+        let t = |c: f64| c.max(0.0).powf(1.0 / cos_theta.abs().max(1e-3));
+        Color::new(t(self.tint.x), t(self.tint.y), t(self.tint.z))
+    }
+}
+
+impl Material for Coated {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
+        if !rec.front_face {
+            return self
+                .base
+                .scatter(r_in, rec, attenuation, scattered, sampler);
+        }
+        let n = rec.normal;
+        let unit_direction = r_in.direction().unit_vector();
+        let cos_o = dot(&-unit_direction, &n);
+        if sampler.get_1d() < fresnel_dielectric(cos_o, self.ior) {
+            *attenuation = Color::new(1.0, 1.0, 1.0);
+            *scattered = Ray::new(rec.p, reflect(unit_direction, n), r_in.time());
+            return true;
+        }
+
+        let mut direction = refract(unit_direction, n, 1.0 / self.ior);
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        for _ in 0..MAX_COATING_BOUNCES {
+            throughput = throughput * self.transmittance(dot(&direction, &n));
+            let inside = Ray::new(rec.p, direction, r_in.time());
+            let mut base_attenuation = Color::default();
+            if !self
+                .base
+                .scatter(&inside, rec, &mut base_attenuation, scattered, sampler)
+            {
+                return false;
+            }
+            throughput = throughput * base_attenuation;
+            let up = scattered.direction().unit_vector();
+            let cos_i = dot(&up, &n);
+            if cos_i <= 0.0 {
+                // Transmitted by the base, out through the bottom of the layer.
+                *attenuation = throughput;
+                return true;
+            }
+            throughput = throughput * self.transmittance(cos_i);
+            if sampler.get_1d() < fresnel_dielectric(cos_i, 1.0 / self.ior) {
+                direction = reflect(up, n);
+            } else {
+                *attenuation = throughput;
+                *scattered = Ray::new(rec.p, refract(up, -n, self.ior), r_in.time());
+                return true;
+            }
+        }
+        false
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.base.albedo(rec)
+    }
+
+    fn medium(&self, wavelength: Option<f64>) -> Option<Medium> {
+        self.base.medium(wavelength)
+    }
+
+    fn dispersive(&self) -> bool {
+        self.base.dispersive()
+    }
+}
+
+// Tints a material by the colour interpolated from the vertices of a mesh,
+// such as a scan with per-vertex colour. Hits without a vertex colour see
+// the base material unchanged.
+pub struct VertexColored {
+    pub base: Arc<dyn Material + Send + Sync>,
+}
+
+impl VertexColored {
+    pub fn new(base: Arc<dyn Material + Send + Sync>) -> VertexColored {
+        VertexColored { base }
+    }
+}
+
+impl Material for VertexColored {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
+        if !self
+            .base
+            .scatter(r_in, rec, attenuation, scattered, sampler)
+        {
+            return false;
+        }
+        if let Some(color) = rec.vertex_color {
+            *attenuation = *attenuation * color;
+        }
+        true
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Option<Color> {
+        let f = self.base.eval(r_in, rec, scattered)?;
+        Some(rec.vertex_color.map_or(f, |color| f * color))
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        self.base.scattering_pdf(r_in, rec, scattered)
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        let albedo = self.base.albedo(rec);
+        rec.vertex_color.map_or(albedo, |color| albedo * color)
+    }
+
+    fn medium(&self, wavelength: Option<f64>) -> Option<Medium> {
+        self.base.medium(wavelength)
+    }
+
+    fn dispersive(&self) -> bool {
+        self.base.dispersive()
+    }
+}
@@ -0,0 +1,47 @@
+pub mod aabb;
+pub mod aov;
+pub mod bezier;
+pub mod bump;
+pub mod bvh;
+pub mod camera;
+pub mod cone;
+pub mod csg;
+pub mod curve;
+pub mod cylinder;
+pub mod denoise;
+pub mod disk;
+pub mod film;
+pub mod filter;
+pub mod hair;
+pub mod heightfield;
+pub mod hittable;
+pub mod hittable_list;
+pub mod implicit;
+pub mod interval;
+pub mod layered;
+pub mod material;
+pub mod medium;
+pub mod merl;
+pub mod microfacet;
+pub mod moving_sphere;
+pub mod onb;
+pub mod particles;
+pub mod plane;
+pub mod ply;
+pub mod principled;
+pub mod progress;
+pub mod quadric;
+pub mod ray;
+pub mod render;
+pub mod roots;
+pub mod rtweekend;
+pub mod sampler;
+pub mod scene;
+pub mod sdf;
+pub mod spectrum;
+pub mod sphere;
+pub mod subdivision;
+pub mod texture;
+pub mod torus;
+pub mod triangle;
+pub mod vec3;
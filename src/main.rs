@@ -1,52 +1,49 @@
-mod aabb;
-mod bvh;
-mod camera;
-mod hittable;
-mod hittable_list;
-mod material;
-mod moving_sphere;
-mod ray;
-mod rtweekend;
-mod sphere;
-mod vec3;
-
-use bvh::*;
-use camera::*;
-use hittable::*;
-use hittable_list::*;
-use image::{ImageBuffer, Rgb, RgbImage};
-use moving_sphere::MovingSphere;
-use ray::*;
-use rayon::prelude::{IntoParallelIterator, ParallelIterator};
-use rtweekend::*;
-use sphere::*;
+use image::{DynamicImage, ImageBuffer, Luma};
+use raytracing::aabb::Aabb;
+use raytracing::aov::*;
+use raytracing::bezier::{load_bpt, tessellate, BezierPatch};
+use raytracing::bump::{BumpMap, NormalMap};
+use raytracing::camera::*;
+use raytracing::cone::Cone;
+use raytracing::csg::Csg;
+use raytracing::curve::{Curve, CurveKind};
+use raytracing::cylinder::Cylinder;
+use raytracing::denoise::*;
+use raytracing::disk::Disk;
+use raytracing::film::*;
+use raytracing::filter::*;
+use raytracing::hair::Hair;
+use raytracing::heightfield::Heightfield;
+use raytracing::hittable::*;
+use raytracing::hittable_list::*;
+use raytracing::implicit::{Implicit, ImplicitFunction};
+use raytracing::interval::Interval;
+use raytracing::layered::{Coated, MixMaterial};
+use raytracing::material::{
+    Dielectric, DiffuseTransmitter, Lambertian, Material, Metal, Microfacet, Subsurface,
+    ThinDielectric,
+};
+use raytracing::merl::Merl;
+use raytracing::moving_sphere::MovingSphere;
+use raytracing::particles::ParticleSet;
+use raytracing::plane::Plane;
+use raytracing::principled::Principled;
+use raytracing::quadric::Quadric;
+use raytracing::render::*;
+use raytracing::rtweekend::*;
+use raytracing::sampler::*;
+use raytracing::scene::Scene;
+use raytracing::sdf::{
+    BoxSdf, Mandelbulb, Repeat, Sdf, SdfHittable, SmoothUnion, SphereSdf, Translate, Twist,
+};
+use raytracing::sphere::*;
+use raytracing::subdivision::PolygonMesh;
+use raytracing::texture::*;
+use raytracing::torus::Torus;
+use raytracing::triangle::TriangleMesh;
+use raytracing::vec3::*;
 use std::sync::Arc;
-use vec3::*;
-
-use crate::material::{Dielectric, Lambertian, Metal};
-
-fn ray_color(r: &Ray, world: Arc<dyn Hittable + Send + Sync>, depth: i32) -> Color {
-    let mut rec = HitRecord::default();
-
-    if depth <= 0 {
-        return Color::default();
-    }
-
-    if world.hit(r, 0.001, INFINITY, &mut rec) {
-        let mut scattered = Ray::new(Vec3::default(), Vec3::default(), 0.0);
-        let mut attenuation = Color::default();
-        if rec
-            .mat_ptr
-            .scatter(r, &rec, &mut attenuation, &mut scattered)
-        {
-            return attenuation * ray_color(&scattered, world, depth - 1);
-        }
-        return Color::default();
-    }
-    let unit_direction = r.direction().unit_vector();
-    let t = 0.5 * (unit_direction.y + 1.0);
-    Color::new(1.0, 1.0, 1.0) * (1.0 - t) + Color::new(0.5, 0.7, 1.0) * t
-}
+use std::time::Duration;
 
 fn random_scene() -> Arc<dyn Hittable + Send + Sync> {
     let mut world = HittableList::default();
@@ -110,7 +107,1100 @@ fn random_scene() -> Arc<dyn Hittable + Send + Sync> {
         )));
     }
 
-    Arc::new(BVHNode::new(&world, 0.0, 1.0))
+    Arc::new(Scene::new(&world, 0.0, 1.0))
+}
+
+// Row of GGX conductors, roughness increasing from left to right.
+fn metals() -> Arc<dyn Hittable + Send + Sync> {
+    let mut world = HittableList::default();
+    let ground_material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    world.add(Arc::new(Sphere::new(
+        Point3::default().set_y(-1000.0),
+        1000.0,
+        ground_material,
+    )));
+
+    let metals: [fn(f64) -> Microfacet; 5] = [
+        Microfacet::gold,
+        Microfacet::copper,
+        Microfacet::aluminium,
+        Microfacet::silver,
+        Microfacet::iron,
+    ];
+    for (i, metal) in metals.iter().enumerate() {
+        let roughness = i as f64 * 0.15;
+        world.add(Arc::new(Sphere::new(
+            Point3::new(i as f64 * 2.2 - 4.4, 0.8, 0.0),
+            0.8,
+            Arc::new(metal(roughness)),
+        )));
+    }
+
+    Arc::new(Scene::new(&world, 0.0, 1.0))
+}
+
+// The principled material: plastic, brushed gold, car paint, velvet and glass.
+fn principled_spheres() -> Arc<dyn Hittable + Send + Sync> {
+    let mut world = HittableList::default();
+    let checker = Arc::new(CheckerTexture::new(
+        Arc::new(SolidColor::new(Color::new(0.2, 0.3, 0.1))),
+        Arc::new(SolidColor::new(Color::new(0.9, 0.9, 0.9))),
+        10.0,
+    ));
+    world.add(Arc::new(Sphere::new(
+        Point3::default().set_y(-1000.0),
+        1000.0,
+        Arc::new(Principled::new(checker)),
+    )));
+
+    let color = |r, g, b| -> Arc<dyn Texture + Send + Sync> {
+        Arc::new(SolidColor::new(Color::new(r, g, b)))
+    };
+    let materials = [
+        Principled {
+            roughness: SolidColor::uniform(0.3),
+            ..Principled::new(color(0.8, 0.1, 0.1))
+        },
+        Principled {
+            metallic: SolidColor::uniform(1.0),
+            roughness: SolidColor::uniform(0.4),
+            ..Principled::new(color(1.0, 0.77, 0.34))
+        },
+        Principled {
+            clearcoat: SolidColor::uniform(1.0),
+            clearcoat_roughness: SolidColor::uniform(0.05),
+            ..Principled::new(color(0.05, 0.1, 0.5))
+        },
+        Principled {
+            roughness: SolidColor::uniform(1.0),
+            sheen: SolidColor::uniform(1.0),
+            ..Principled::new(color(0.4, 0.05, 0.3))
+        },
+        Principled {
+            roughness: SolidColor::uniform(0.0),
+            transmission: SolidColor::uniform(1.0),
+            ..Principled::new(color(1.0, 1.0, 1.0))
+        },
+    ];
+    for (i, material) in materials.into_iter().enumerate() {
+        world.add(Arc::new(Sphere::new(
+            Point3::new(i as f64 * 2.2 - 4.4, 0.8, 0.0),
+            0.8,
+            Arc::new(material),
+        )));
+    }
+
+    Arc::new(Scene::new(&world, 0.0, 1.0))
+}
+
+// Glass spheres from smooth to frosted in front of a row of coloured balls.
+fn frosted_glass() -> Arc<dyn Hittable + Send + Sync> {
+    let mut world = HittableList::default();
+    let ground_material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    world.add(Arc::new(Sphere::new(
+        Point3::default().set_y(-1000.0),
+        1000.0,
+        ground_material,
+    )));
+
+    for i in 0..4 {
+        let roughness = [0.0, 0.1, 0.25, 0.5][i];
+        world.add(Arc::new(Sphere::new(
+            Point3::new(i as f64 * 2.4 - 3.6, 1.0, 0.0),
+            1.0,
+            Arc::new(Dielectric::rough(1.5, roughness)),
+        )));
+    }
+    for i in 0..16 {
+        let hue = i as f64 / 16.0;
+        let albedo = Color::new(
+            0.5 + 0.5 * (2.0 * PI * hue).cos(),
+            0.5 + 0.5 * (2.0 * PI * (hue + 0.33)).cos(),
+            0.5 + 0.5 * (2.0 * PI * (hue + 0.67)).cos(),
+        );
+        world.add(Arc::new(Sphere::new(
+            Point3::new(i as f64 * 0.7 - 5.25, 0.3, -3.0),
+            0.3,
+            Arc::new(Lambertian::new(albedo)),
+        )));
+    }
+
+    Arc::new(Scene::new(&world, 0.0, 1.0))
+}
+
+// Tinted glass whose colour deepens with thickness, and a bead of liquid in a
+// hollow glass ball, modelled as nested volumes.
+fn coloured_glass() -> Arc<dyn Hittable + Send + Sync> {
+    let mut world = HittableList::default();
+    let checker = Arc::new(CheckerTexture::new(
+        Arc::new(SolidColor::new(Color::new(0.2, 0.3, 0.1))),
+        Arc::new(SolidColor::new(Color::new(0.9, 0.9, 0.9))),
+        10.0,
+    ));
+    world.add(Arc::new(Sphere::new(
+        Point3::default().set_y(-1000.0),
+        1000.0,
+        Arc::new(Principled::new(checker)),
+    )));
+
+    for i in 0..3 {
+        let radius = [0.4, 0.7, 1.0][i];
+        world.add(Arc::new(Sphere::new(
+            Point3::new(i as f64 * 2.2 - 5.2, radius, 0.0),
+            radius,
+            Arc::new(Dielectric::new(1.5).with_tint(Color::new(0.9, 0.3, 0.2), 1.0)),
+        )));
+    }
+
+    let centre = Point3::new(3.2, 1.2, 0.0);
+    let glass = Dielectric::new(1.5).with_tint(Color::new(0.8, 0.95, 0.9), 1.0);
+    world.add(Arc::new(Sphere::new(
+        centre,
+        1.2,
+        Arc::new(glass.with_priority(1)),
+    )));
+    let air = Dielectric::new(1.0).with_priority(2);
+    world.add(Arc::new(Sphere::new(centre, 1.05, Arc::new(air))));
+    let liquid = Dielectric::new(1.33)
+        .with_tint(Color::new(0.3, 0.5, 0.9), 0.5)
+        .with_priority(3);
+    world.add(Arc::new(Sphere::new(
+        centre - Vec3::default().set_y(0.45),
+        0.6,
+        Arc::new(liquid),
+    )));
+
+    Arc::new(Scene::new(&world, 0.0, 1.0))
+}
+
+// Crown glass, flint glass and diamond rendered spectrally, so the flint and
+// diamond split the checkerboard seen through them into colour fringes.
+fn dispersion() -> Arc<dyn Hittable + Send + Sync> {
+    let mut world = HittableList::default();
+    let checker = Arc::new(CheckerTexture::new(
+        Arc::new(SolidColor::new(Color::new(0.05, 0.05, 0.05))),
+        Arc::new(SolidColor::new(Color::new(0.95, 0.95, 0.95))),
+        10.0,
+    ));
+    world.add(Arc::new(Sphere::new(
+        Point3::default().set_y(-1000.0),
+        1000.0,
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, 0.0, -1006.0),
+        1000.0,
+        Arc::new(Principled::new(checker)),
+    )));
+
+    let glasses = [
+        Dielectric::crown_glass(),
+        Dielectric::flint_glass(),
+        Dielectric::diamond(),
+    ];
+    for (i, glass) in glasses.into_iter().enumerate() {
+        world.add(Arc::new(Sphere::new(
+            Point3::new(i as f64 * 2.6 - 2.6, 1.2, 0.0),
+            1.2,
+            Arc::new(glass),
+        )));
+    }
+
+    Arc::new(Scene::new(&world, 0.0, 1.0))
+}
+
+// Marble, wax and skin spheres lit by the sky, rendered with a random walk
+// through their interiors.
+fn subsurface() -> Arc<dyn Hittable + Send + Sync> {
+    let mut world = HittableList::default();
+    world.add(Arc::new(Sphere::new(
+        Point3::default().set_y(-1000.0),
+        1000.0,
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+    )));
+
+    let materials = [
+        Subsurface::new(
+            Color::new(0.999, 0.998, 0.995),
+            Color::new(0.15, 0.15, 0.15),
+            1.5,
+        ),
+        Subsurface::new(
+            Color::new(0.995, 0.97, 0.85),
+            Color::new(0.3, 0.2, 0.12),
+            1.45,
+        )
+        .with_roughness(0.3),
+        Subsurface::new(Color::new(0.99, 0.9, 0.8), Color::new(0.4, 0.12, 0.06), 1.4)
+            .with_anisotropy(0.8)
+            .with_roughness(0.4),
+    ];
+    for (i, material) in materials.into_iter().enumerate() {
+        world.add(Arc::new(Sphere::new(
+            Point3::new(i as f64 * 2.6 - 2.6, 1.0, 0.0),
+            1.0,
+            Arc::new(material),
+        )));
+    }
+
+    Arc::new(Scene::new(&world, 0.0, 1.0))
+}
+
+// Single-sided sheets: a soap bubble, a paper lantern and a leaf-green shell,
+// in front of a row of coloured balls.
+fn thin_sheets() -> Arc<dyn Hittable + Send + Sync> {
+    let mut world = HittableList::default();
+    world.add(Arc::new(Sphere::new(
+        Point3::default().set_y(-1000.0),
+        1000.0,
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+    )));
+
+    world.add(Arc::new(Sphere::new(
+        Point3::new(-2.6, 1.0, 0.0),
+        1.0,
+        Arc::new(
+            ThinDielectric::new(1.33)
+                .with_albedo(Color::new(1.0, 1.0, 1.0), Color::new(0.95, 0.9, 1.0)),
+        ),
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, 1.0, 0.0),
+        1.0,
+        Arc::new(DiffuseTransmitter::new(
+            Color::new(0.9, 0.88, 0.8),
+            Color::new(0.9, 0.6, 0.3),
+            0.5,
+        )),
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(2.6, 1.0, 0.0),
+        1.0,
+        Arc::new(DiffuseTransmitter::new(
+            Color::new(0.15, 0.4, 0.08),
+            Color::new(0.35, 0.6, 0.1),
+            0.4,
+        )),
+    )));
+    for i in 0..16 {
+        let hue = i as f64 / 16.0;
+        let albedo = Color::new(
+            0.5 + 0.5 * (2.0 * PI * hue).cos(),
+            0.5 + 0.5 * (2.0 * PI * (hue + 0.33)).cos(),
+            0.5 + 0.5 * (2.0 * PI * (hue + 0.67)).cos(),
+        );
+        world.add(Arc::new(Sphere::new(
+            Point3::new(i as f64 * 0.7 - 5.25, 0.3, -3.0),
+            0.3,
+            Arc::new(Lambertian::new(albedo)),
+        )));
+    }
+
+    Arc::new(Scene::new(&world, 0.0, 1.0))
+}
+
+// Height field of a grid of round dimples in (u, v) space.
+struct Dimples {
+    cells_u: f64,
+    cells_v: f64,
+}
+
+impl Dimples {
+    // Height and its gradient in cell units at (u, v).
+    fn eval(&self, u: f64, v: f64) -> (f64, f64, f64) {
+        let x = (u * self.cells_u).fract() - 0.5;
+        let y = (v * self.cells_v).fract() - 0.5;
+        let r2 = x * x + y * y;
+        if r2 > 0.16 {
+            return (0.0, 0.0, 0.0);
+        }
+        let k = 0.16 - r2;
+        (-k, 2.0 * x, 2.0 * y)
+    }
+}
+
+impl Texture for Dimples {
+    fn value(&self, u: f64, v: f64, _p: &Point3) -> Color {
+        let (h, _, _) = self.eval(u, v);
+        Color::new(h, h, h)
+    }
+}
+
+// The same dimples encoded as a tangent-space normal map.
+struct DimpleNormals(Dimples);
+
+impl Texture for DimpleNormals {
+    fn value(&self, u: f64, v: f64, _p: &Point3) -> Color {
+        let (_, dx, dy) = self.0.eval(u, v);
+        let n = Vec3::new(-dx * 2.0, -dy * 2.0, 1.0).unit_vector();
+        (n + Color::new(1.0, 1.0, 1.0)) * 0.5
+    }
+}
+
+// Fine ripples in space, for a hammered finish.
+struct Ripples {
+    frequency: f64,
+}
+
+impl Texture for Ripples {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        let f = self.frequency;
+        let h = (f * p.x).sin() * (f * p.y).sin() + (f * 1.3 * p.z).sin() * (f * 0.7 * p.x).cos();
+        Color::new(h, h, h)
+    }
+}
+
+// Bump and normal mapping: a dimpled ball both ways, a hammered copper ball
+// and a rippled glass ball.
+fn mapped_surfaces() -> Arc<dyn Hittable + Send + Sync> {
+    let mut world = HittableList::default();
+    world.add(Arc::new(Sphere::new(
+        Point3::default().set_y(-1000.0),
+        1000.0,
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+    )));
+
+    let plastic: Arc<dyn Material + Send + Sync> = Arc::new(Principled::new(Arc::new(
+        SolidColor::new(Color::new(0.8, 0.8, 0.8)),
+    )));
+    let dimples = || Dimples {
+        cells_u: 24.0,
+        cells_v: 12.0,
+    };
+    world.add(Arc::new(Sphere::new(
+        Point3::new(-3.9, 1.0, 0.0),
+        1.0,
+        Arc::new(BumpMap::new(plastic.clone(), Arc::new(dimples()), 0.5)),
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(-1.3, 1.0, 0.0),
+        1.0,
+        Arc::new(NormalMap::new(plastic, Arc::new(DimpleNormals(dimples())))),
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(1.3, 1.0, 0.0),
+        1.0,
+        Arc::new(BumpMap::new(
+            Arc::new(Microfacet::copper(0.15)),
+            Arc::new(Ripples { frequency: 12.0 }),
+            0.01,
+        )),
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(3.9, 1.0, 0.0),
+        1.0,
+        Arc::new(BumpMap::new(
+            Arc::new(Dielectric::new(1.5)),
+            Arc::new(Ripples { frequency: 6.0 }),
+            0.02,
+        )),
+    )));
+
+    Arc::new(Scene::new(&world, 0.0, 1.0))
+}
+
+// Irregular patches in space: 1 inside a patch, 0 outside.
+struct Blotches {
+    frequency: f64,
+    coverage: f64,
+}
+
+impl Texture for Blotches {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        let f = self.frequency;
+        let n = (f * p.x).sin() * (f * 1.7 * p.y + 1.3).sin() * (f * 1.3 * p.z + 0.7).sin()
+            + 0.5 * (f * 2.9 * p.x + 2.0).sin() * (f * 3.1 * p.z).sin();
+        let m = if n > 0.5 - self.coverage { 1.0 } else { 0.0 };
+        Color::new(m, m, m)
+    }
+}
+
+// Dust settling on upward facing parts of a sphere, by its v coordinate.
+struct Dust;
+
+impl Texture for Dust {
+    fn value(&self, _u: f64, v: f64, _p: &Point3) -> Color {
+        let m = ((v - 0.6) / 0.3).clamp(0.0, 1.0);
+        Color::new(m, m, m)
+    }
+}
+
+// Layered materials: rusted iron, dusty lacquered plastic, varnished wood-ish
+// diffuse, and a lacquer over a half-and-half mix of gold and paint.
+fn layered_materials() -> Arc<dyn Hittable + Send + Sync> {
+    let mut world = HittableList::default();
+    world.add(Arc::new(Sphere::new(
+        Point3::default().set_y(-1000.0),
+        1000.0,
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+    )));
+
+    let rust = Arc::new(Lambertian::new(Color::new(0.45, 0.18, 0.06)));
+    let rusted_iron = MixMaterial::new(
+        Arc::new(Microfacet::iron(0.2)),
+        rust,
+        Arc::new(Blotches {
+            frequency: 4.0,
+            coverage: 0.4,
+        }),
+    );
+    let lacquered = Arc::new(Coated::new(
+        Arc::new(Lambertian::new(Color::new(0.7, 0.05, 0.05))),
+        1.5,
+    ));
+    let dusty = MixMaterial::new(
+        lacquered,
+        Arc::new(Lambertian::new(Color::new(0.6, 0.58, 0.55))),
+        Arc::new(Dust),
+    );
+    let varnished = Coated::new(Arc::new(Lambertian::new(Color::new(0.6, 0.4, 0.2))), 1.5)
+        .with_tint(Color::new(0.9, 0.7, 0.4));
+    let gold_and_paint = Coated::new(
+        Arc::new(MixMaterial::uniform(
+            Arc::new(Microfacet::gold(0.3)),
+            Arc::new(Lambertian::new(Color::new(0.1, 0.2, 0.6))),
+            0.5,
+        )),
+        1.5,
+    );
+
+    let materials: [Arc<dyn Material + Send + Sync>; 4] = [
+        Arc::new(rusted_iron),
+        Arc::new(dusty),
+        Arc::new(varnished),
+        Arc::new(gold_and_paint),
+    ];
+    for (i, material) in materials.into_iter().enumerate() {
+        world.add(Arc::new(Sphere::new(
+            Point3::new(i as f64 * 2.6 - 3.9, 1.0, 0.0),
+            1.0,
+            material,
+        )));
+    }
+
+    Arc::new(Scene::new(&world, 0.0, 1.0))
+}
+
+fn measured_brdfs() -> Arc<dyn Hittable + Send + Sync> {
+    let mut world = HittableList::default();
+    world.add(Arc::new(Sphere::new(
+        Point3::default().set_y(-1000.0),
+        1000.0,
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+    )));
+
+    // Files from the MERL BRDF database, which isn't distributed with the
+    // renderer; spheres whose file is missing are rendered grey.
+    let paths = [
+        "brdfs/gold-metallic-paint.binary",
+        "brdfs/blue-acrylic.binary",
+        "brdfs/chrome-steel.binary",
+        "brdfs/red-fabric.binary",
+    ];
+    for (i, path) in paths.into_iter().enumerate() {
+        let material: Arc<dyn Material + Send + Sync> = match Merl::load(path) {
+            Ok(merl) => Arc::new(merl),
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+            }
+        };
+        world.add(Arc::new(Sphere::new(
+            Point3::new(i as f64 * 2.6 - 3.9, 1.0, 0.0),
+            1.0,
+            material,
+        )));
+    }
+
+    Arc::new(Scene::new(&world, 0.0, 1.0))
+}
+
+fn analytic_shapes() -> Arc<dyn Hittable + Send + Sync> {
+    let checker = Arc::new(CheckerTexture::new(
+        Arc::new(SolidColor::new(Color::new(0.2, 0.3, 0.1))),
+        Arc::new(SolidColor::new(Color::new(0.9, 0.9, 0.9))),
+        10.0,
+    ));
+    let ground = Arc::new(Plane::new(
+        Point3::default(),
+        Vec3::new(0.0, 1.0, 0.0),
+        Arc::new(Principled::new(checker)),
+    ));
+
+    let up = Vec3::new(0.0, 1.0, 0.0);
+    let mut world = HittableList::default();
+    world.add(Arc::new(Cylinder::new(
+        Point3::new(-4.2, 0.0, 0.0),
+        up,
+        0.7,
+        1.8,
+        Arc::new(Microfacet::copper(0.2)),
+    )));
+    world.add(Arc::new(Cone::new(
+        Point3::new(-1.8, 0.0, 0.0),
+        up,
+        0.9,
+        2.0,
+        Arc::new(Lambertian::new(Color::new(0.7, 0.3, 0.1))),
+    )));
+    world.add(Arc::new(Torus::new(
+        Point3::new(0.6, 1.0, 0.0),
+        Vec3::new(0.0, 0.4, 1.0),
+        0.8,
+        0.25,
+        Arc::new(Microfacet::gold(0.15)),
+    )));
+    world.add(Arc::new(Disk::new(
+        Point3::new(2.9, 1.0, -0.5),
+        Vec3::new(-0.3, 0.2, 1.0),
+        0.9,
+        Arc::new(Lambertian::new(Color::new(0.1, 0.3, 0.7))),
+    )));
+    world.add(Arc::new(Disk::annulus(
+        Point3::new(4.6, 0.9, 0.0),
+        Vec3::new(-0.5, 0.3, 1.0),
+        0.4,
+        0.8,
+        Arc::new(Metal::new(Color::new(0.8, 0.8, 0.8), 0.05)),
+    )));
+
+    // The plane has no bounding box, so the scene keeps it outside the BVH.
+    world.add(ground);
+    Arc::new(Scene::new(&world, 0.0, 1.0))
+}
+
+fn csg_solids() -> Arc<dyn Hittable + Send + Sync> {
+    let mut world = HittableList::default();
+    world.add(Arc::new(Plane::new(
+        Point3::default(),
+        Vec3::new(0.0, 1.0, 0.0),
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+    )));
+
+    // Biconvex lens: the overlap of two spheres.
+    let glass = Arc::new(Dielectric::new(1.5));
+    world.add(Arc::new(Csg::intersection(
+        Arc::new(Sphere::new(Point3::new(-3.9, 1.2, 1.6), 2.0, glass.clone())),
+        Arc::new(Sphere::new(Point3::new(-3.9, 1.2, -1.6), 2.0, glass)),
+    )));
+
+    // Bowl: a spherical shell cut off by the half-space below a plane.
+    let ceramic = Arc::new(Principled::new(Arc::new(SolidColor::new(Color::new(
+        0.8, 0.75, 0.6,
+    )))));
+    let shell = Csg::difference(
+        Arc::new(Sphere::new(
+            Point3::new(-1.2, 1.0, 0.0),
+            1.0,
+            ceramic.clone(),
+        )),
+        Arc::new(Sphere::new(
+            Point3::new(-1.2, 1.0, 0.0),
+            0.9,
+            ceramic.clone(),
+        )),
+    );
+    world.add(Arc::new(Csg::intersection(
+        Arc::new(shell),
+        Arc::new(Plane::new(
+            Point3::new(0.0, 1.3, 0.0),
+            Vec3::new(0.0, 1.0, 0.0).set_x(-0.3).unit_vector(),
+            ceramic,
+        )),
+    )));
+
+    // Drilled block: a cylinder with two holes bored across it.
+    let steel = Arc::new(Microfacet::iron(0.25));
+    let drill = |axis: Vec3| -> Arc<dyn Hittable + Send + Sync> {
+        Arc::new(Cylinder::new(
+            Point3::new(1.5, 1.0, 0.0) - axis * 1.5,
+            axis,
+            0.35,
+            3.0,
+            steel.clone(),
+        ))
+    };
+    let block = Csg::difference(
+        Arc::new(Cylinder::new(
+            Point3::new(1.5, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            1.0,
+            2.0,
+            steel.clone(),
+        )),
+        Arc::new(Csg::union(
+            drill(Vec3::new(0.0, 0.0, 1.0)),
+            drill(Vec3::new(1.0, 0.0, 0.0)),
+        )),
+    );
+    world.add(Arc::new(block));
+
+    // Capsule: a cylinder with a sphere on each end.
+    let paint = Arc::new(Lambertian::new(Color::new(0.1, 0.3, 0.7)));
+    let capsule = Csg::union(
+        Arc::new(Cylinder::new(
+            Point3::new(4.2, 0.6, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            0.6,
+            1.2,
+            paint.clone(),
+        )),
+        Arc::new(Csg::union(
+            Arc::new(Sphere::new(Point3::new(4.2, 0.6, 0.0), 0.6, paint.clone())),
+            Arc::new(Sphere::new(Point3::new(4.2, 1.8, 0.0), 0.6, paint)),
+        )),
+    );
+    world.add(Arc::new(capsule));
+
+    Arc::new(Scene::new(&world, 0.0, 1.0))
+}
+
+fn distance_fields() -> Arc<dyn Hittable + Send + Sync> {
+    let mut world = HittableList::default();
+    world.add(Arc::new(Plane::new(
+        Point3::default(),
+        Vec3::new(0.0, 1.0, 0.0),
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+    )));
+    let around = |center: Point3, half: Vec3| Aabb::new(center - half, center + half);
+
+    // Three spheres blended into one glass blob.
+    let ball = |center: Point3, radius: f64| -> Arc<dyn Sdf + Send + Sync> {
+        Arc::new(Translate {
+            sdf: Arc::new(SphereSdf { radius }),
+            offset: center,
+        })
+    };
+    let blob = SmoothUnion {
+        a: Arc::new(SmoothUnion {
+            a: ball(Point3::new(-4.2, 0.7, 0.0), 0.7),
+            b: ball(Point3::new(-3.6, 1.2, 0.2), 0.5),
+            k: 0.4,
+        }),
+        b: ball(Point3::new(-4.3, 1.6, -0.1), 0.4),
+        k: 0.4,
+    };
+    world.add(Arc::new(SdfHittable::new(
+        Arc::new(blob),
+        around(Point3::new(-4.0, 1.1, 0.0), Vec3::new(1.2, 1.2, 1.0)),
+        Arc::new(Dielectric::new(1.5)),
+    )));
+
+    // Rounded box twisted about its vertical axis.
+    let twisted = Translate {
+        sdf: Arc::new(Twist {
+            sdf: Arc::new(BoxSdf {
+                half_extents: Vec3::new(0.5, 0.9, 0.5),
+                radius: 0.05,
+            }),
+            rate: 1.2,
+        }),
+        offset: Point3::new(-1.4, 0.9, 0.0),
+    };
+    world.add(Arc::new(
+        SdfHittable::new(
+            Arc::new(twisted),
+            around(Point3::new(-1.4, 0.9, 0.0), Vec3::new(0.75, 0.95, 0.75)),
+            Arc::new(Microfacet::copper(0.25)),
+        )
+        .with_step_scale(0.7),
+    ));
+
+    let mandelbulb = Translate {
+        sdf: Arc::new(Mandelbulb::default()),
+        offset: Point3::new(1.3, 1.2, 0.0),
+    };
+    world.add(Arc::new(SdfHittable::new(
+        Arc::new(mandelbulb),
+        around(Point3::new(1.3, 1.2, 0.0), Vec3::new(1.25, 1.25, 1.25)),
+        Arc::new(Lambertian::new(Color::new(0.8, 0.5, 0.3))),
+    )));
+
+    // A 3 x 3 x 3 lattice of spheres from one repeated sphere; the bounding box
+    // follows cell boundaries so no sphere is cut off.
+    let lattice = Translate {
+        sdf: Arc::new(Repeat {
+            sdf: Arc::new(SphereSdf { radius: 0.15 }),
+            spacing: Vec3::new(0.5, 0.5, 0.5),
+        }),
+        offset: Point3::new(4.2, 1.0, 0.0),
+    };
+    world.add(Arc::new(SdfHittable::new(
+        Arc::new(lattice),
+        around(Point3::new(4.2, 1.0, 0.0), Vec3::new(0.75, 0.75, 0.75)),
+        Arc::new(Lambertian::new(Color::new(0.1, 0.3, 0.7))),
+    )));
+
+    Arc::new(Scene::new(&world, 0.0, 1.0))
+}
+
+// Tanglecube x^4 - 5x^2 + y^4 - 5y^2 + z^4 - 5z^2 + 11.8 = 0, scaled about
+// `center`; it fits within 2.3 of the origin before scaling.
+struct Tanglecube {
+    center: Point3,
+    scale: f64,
+}
+
+impl ImplicitFunction for Tanglecube {
+    fn value(&self, p: Point3) -> f64 {
+        let q = (p - self.center) / self.scale;
+        let g = |x: f64| x * x * x * x - 5.0 * x * x;
+        g(q.x) + g(q.y) + g(q.z) + 11.8
+    }
+
+    fn bounds(&self, x: Interval, y: Interval, z: Interval) -> Interval {
+        // x^4 - 5x^2 as (x^2 - 2.5)^2 - 6.25, which interval arithmetic bounds
+        // more tightly.
+        let g = |x: Interval, c: f64| ((x - c) * (1.0 / self.scale)).sqr() - 2.5;
+        g(x, self.center.x).sqr()
+            + g(y, self.center.y).sqr()
+            + g(z, self.center.z).sqr()
+            + (11.8 - 3.0 * 6.25)
+    }
+}
+
+fn quadrics_and_implicits() -> Arc<dyn Hittable + Send + Sync> {
+    let mut world = HittableList::default();
+    world.add(Arc::new(Plane::new(
+        Point3::default(),
+        Vec3::new(0.0, 1.0, 0.0),
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+    )));
+
+    world.add(Arc::new(Quadric::ellipsoid(
+        Point3::new(-4.2, 0.8, 0.0),
+        Vec3::new(0.9, 0.8, 0.5),
+        Arc::new(Lambertian::new(Color::new(0.7, 0.3, 0.1))),
+    )));
+    world.add(Arc::new(Quadric::paraboloid(
+        Point3::new(-2.1, 0.3, 0.0),
+        0.9,
+        1.4,
+        Arc::new(Microfacet::gold(0.2)),
+    )));
+    world.add(Arc::new(Quadric::hyperboloid(
+        Point3::new(0.0, 1.0, 0.0),
+        0.45,
+        0.85,
+        2.0,
+        Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.75))),
+    )));
+    world.add(Arc::new(Quadric::two_sheet_hyperboloid(
+        Point3::new(2.1, 1.1, 0.0),
+        0.25,
+        0.8,
+        2.0,
+        Arc::new(Lambertian::new(Color::new(0.1, 0.3, 0.7))),
+    )));
+
+    let center = Point3::new(4.2, 1.1, 0.0);
+    let scale = 0.45;
+    let extent = Vec3::new(2.3, 2.3, 2.3) * scale;
+    world.add(Arc::new(Implicit::new(
+        Arc::new(Tanglecube { center, scale }),
+        Aabb::new(center - extent, center + extent),
+        Arc::new(Microfacet::copper(0.3)),
+    )));
+
+    Arc::new(Scene::new(&world, 0.0, 1.0))
+}
+
+fn patches_and_subdivision() -> Arc<dyn Hittable + Send + Sync> {
+    let mut world = HittableList::default();
+    world.add(Arc::new(Plane::new(
+        Point3::default(),
+        Vec3::new(0.0, 1.0, 0.0),
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+    )));
+
+    // A cube cage, once and four times subdivided.
+    let cube = |center: Point3, size: f64| {
+        let positions = (0..8)
+            .map(|i| {
+                let corner = Vec3::new(
+                    (i & 1) as f64 - 0.5,
+                    ((i >> 1) & 1) as f64 - 0.5,
+                    ((i >> 2) & 1) as f64 - 0.5,
+                );
+                center + corner * size
+            })
+            .collect();
+        let faces = vec![
+            vec![0, 2, 3, 1],
+            vec![4, 5, 7, 6],
+            vec![0, 1, 5, 4],
+            vec![2, 6, 7, 3],
+            vec![0, 4, 6, 2],
+            vec![1, 3, 7, 5],
+        ];
+        PolygonMesh::new(positions, faces)
+    };
+    let coarse = cube(Point3::new(-4.2, 0.75, 0.0), 1.5).subdivide(1);
+    let smooth = cube(Point3::new(-2.0, 0.95, 0.0), 1.9).subdivide(4);
+    world.objects.extend(
+        coarse
+            .triangulate(Arc::new(Lambertian::new(Color::new(0.7, 0.3, 0.1))))
+            .triangles()
+            .objects,
+    );
+    world.objects.extend(
+        smooth
+            .triangulate(Arc::new(Microfacet::gold(0.2)))
+            .triangles()
+            .objects,
+    );
+
+    // The Utah teapot, which isn't distributed with the renderer, turned from
+    // z up to y up.
+    let path = "models/teapot.bpt";
+    match load_bpt(path) {
+        Ok(patches) => {
+            let patches: Vec<BezierPatch> = patches
+                .into_iter()
+                .map(|patch| {
+                    BezierPatch::new(patch.control.map(|row| {
+                        row.map(|c| Point3::new(0.9, 0.0, 0.0) + Vec3::new(c.x, c.z, -c.y) * 0.5)
+                    }))
+                })
+                .collect();
+            let teapot = tessellate(
+                &patches,
+                1e-3,
+                Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.75))),
+            );
+            world.objects.extend(teapot.triangles().objects);
+        }
+        Err(e) => eprintln!("{}: {}", path, e),
+    }
+
+    // A single wavy patch.
+    let mut control = [[Point3::default(); 4]; 4];
+    for (j, row) in control.iter_mut().enumerate() {
+        for (i, c) in row.iter_mut().enumerate() {
+            let wave = if (i + j) % 2 == 0 { 0.4 } else { -0.4 };
+            *c = Point3::new(
+                4.2 + (i as f64 - 1.5) * 0.6,
+                0.1 + j as f64 * 0.6,
+                wave - j as f64 * 0.3,
+            );
+        }
+    }
+    let wave = tessellate(
+        &[BezierPatch::new(control)],
+        1e-3,
+        Arc::new(Lambertian::new(Color::new(0.1, 0.3, 0.7))),
+    );
+    world.objects.extend(wave.triangles().objects);
+
+    Arc::new(Scene::new(&world, 0.0, 1.0))
+}
+
+fn hair_and_grass() -> Arc<dyn Hittable + Send + Sync> {
+    let mut world = HittableList::default();
+    world.add(Arc::new(Plane::new(
+        Point3::default(),
+        Vec3::new(0.0, 1.0, 0.0),
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+    )));
+
+    // Balls covered in strands growing out of them and drooping a little.
+    let mut furry =
+        |center: Point3, radius: f64, length: f64, strands: usize, hair: Hair, skin: Color| {
+            world.add(Arc::new(Sphere::new(
+                center,
+                radius,
+                Arc::new(Lambertian::new(skin)),
+            )));
+            let hair: Arc<dyn Material + Send + Sync> = Arc::new(hair);
+            for _ in 0..strands {
+                let n = random_unit_vector();
+                let root = center + n * radius;
+                let jitter = || Vec3::rand_vec3(-0.1, 0.1) * length;
+                let droop = Vec3::new(0.0, -0.25 * length, 0.0);
+                let control = [
+                    root,
+                    root + n * (length / 3.0),
+                    root + n * (2.0 * length / 3.0) + droop * 0.3 + jitter(),
+                    root + n * length + droop + jitter(),
+                ];
+                let curve = Curve::new(control, 0.012, 0.003, CurveKind::Cylinder, hair.clone());
+                world.objects.extend(curve.split(2).objects);
+            }
+        };
+    furry(
+        Point3::new(-3.3, 1.1, 0.0),
+        0.75,
+        0.45,
+        12000,
+        Hair::from_melanin(1.3, 0.0, 0.25, 0.3),
+        Color::new(0.2, 0.12, 0.08),
+    );
+    furry(
+        Point3::new(-0.2, 1.0, 0.0),
+        0.45,
+        0.55,
+        10000,
+        Hair::from_color(Color::new(0.6, 0.15, 0.05), 0.3, 0.3),
+        Color::new(0.4, 0.1, 0.05),
+    );
+
+    // Grass blades tapering to a point, bending over their faces and twisting
+    // a little towards the tip.
+    let grass: Arc<dyn Material + Send + Sync> =
+        Arc::new(Lambertian::new(Color::new(0.2, 0.45, 0.1)));
+    for _ in 0..12000 {
+        let root = Point3::new(rand_double(2.0, 4.8), 0.0, rand_double(-1.5, 1.5));
+        let height = rand_double(0.4, 0.9);
+        let facing = rand_double(0.0, 2.0 * PI);
+        let twist = facing + rand_double(-0.6, 0.6);
+        let normals = [
+            Vec3::new(facing.cos(), 0.0, facing.sin()),
+            Vec3::new(twist.cos(), 0.0, twist.sin()),
+        ];
+        let bend = normals[0] * rand_double(-1.0, 1.0) * height;
+        let control = [
+            root,
+            root + Vec3::new(0.0, height / 3.0, 0.0),
+            root + Vec3::new(0.0, 2.0 * height / 3.0, 0.0) + bend * 0.3,
+            root + Vec3::new(0.0, height, 0.0) + bend * 0.6,
+        ];
+        let curve = Curve::ribbon(control, 0.025, 0.0, normals, grass.clone());
+        world.objects.extend(curve.split(2).objects);
+    }
+
+    Arc::new(Scene::new(&world, 0.0, 1.0))
+}
+
+fn terrain() -> Arc<dyn Hittable + Send + Sync> {
+    let mut world = HittableList::default();
+    world.add(Arc::new(Plane::new(
+        Point3::default(),
+        Vec3::new(0.0, 1.0, 0.0),
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+    )));
+
+    // A 16-bit heightmap of a mountain roughened by waves at random angles,
+    // shorter ones weaker, as a terrain file would be read.
+    let waves: Vec<(f64, f64, f64, f64)> = (0..10)
+        .map(|k| {
+            let frequency = 4.0 * 1.8f64.powi(k);
+            let angle = rand_double(0.0, 2.0 * PI);
+            (
+                frequency * angle.cos(),
+                frequency * angle.sin(),
+                rand_double(0.0, 2.0 * PI),
+                0.6f64.powi(k),
+            )
+        })
+        .collect();
+    let size = 512;
+    let heightmap = ImageBuffer::from_fn(size, size, |i, j| {
+        let (x, z) = (i as f64 / size as f64, j as f64 / size as f64);
+        let mountain = (-((x - 0.5).powi(2) + (z - 0.3).powi(2)) * 10.0).exp();
+        let rough: f64 = waves
+            .iter()
+            .map(|(fx, fz, phase, amplitude)| amplitude * (fx * x + fz * z + phase).sin())
+            .sum();
+        let h = (mountain * (0.8 + 0.15 * rough)).clamp(0.0, 1.0);
+        Luma([(h * 65535.0) as u16])
+    });
+    world.add(Arc::new(
+        Heightfield::from_image(
+            &DynamicImage::ImageLuma16(heightmap),
+            Point3::new(-8.0, 0.0, -8.0),
+            Vec3::new(16.0, 4.0, 12.0),
+            Arc::new(Lambertian::new(Color::new(0.45, 0.4, 0.3))),
+        )
+        .unwrap(),
+    ));
+
+    Arc::new(Scene::new(&world, 0.0, 1.0))
+}
+
+fn particles() -> Arc<dyn Hittable + Send + Sync> {
+    let mut world = HittableList::default();
+    world.add(Arc::new(Plane::new(
+        Point3::default(),
+        Vec3::new(0.0, 1.0, 0.0),
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+    )));
+
+    // A dust devil of 300,000 grains, widening as it rises out of a heap, as a
+    // simulation would dump them.
+    let count = 300_000;
+    let mut positions = Vec::with_capacity(count);
+    let mut radii = Vec::with_capacity(count);
+    let mut material_indices = Vec::with_capacity(count);
+    for _ in 0..count {
+        let h = 3.2 * rand_01().powf(1.5);
+        let angle = rand_double(0.0, 2.0 * PI);
+        let spread = 0.25 + 0.5 * h;
+        let r = spread * (1.0 + 0.25 * rand_double(-1.0, 1.0)) + 1.5 * (-4.0 * h).exp() * rand_01();
+        positions.push([
+            (r * angle.cos()) as f32,
+            (h + 0.03) as f32,
+            (r * angle.sin()) as f32,
+        ]);
+        radii.push(rand_double(0.015, 0.035) as f32);
+        material_indices.push(if rand_01() < 0.02 {
+            2
+        } else if rand_01() < 0.3 {
+            1
+        } else {
+            0
+        });
+    }
+    let materials: Vec<Arc<dyn Material + Sync + Send>> = vec![
+        Arc::new(Lambertian::new(Color::new(0.75, 0.6, 0.4))),
+        Arc::new(Lambertian::new(Color::new(0.45, 0.3, 0.2))),
+        Arc::new(Metal::new(Color::new(0.9, 0.85, 0.7), 0.2)),
+    ];
+    world.add(Arc::new(ParticleSet::new(
+        positions,
+        radii,
+        material_indices,
+        materials,
+    )));
+
+    Arc::new(Scene::new(&world, 0.0, 1.0))
+}
+
+fn scanned_meshes() -> Arc<dyn Hittable + Send + Sync> {
+    let mut world = HittableList::default();
+    world.add(Arc::new(Plane::new(
+        Point3::default(),
+        Vec3::new(0.0, 1.0, 0.0),
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+    )));
+
+    // A sphere painted with vertex colours over a white base, and a knot whose
+    // facets are smoothed once the STL's repeated corners are merged.
+    let path = "models/icosphere.ply";
+    match TriangleMesh::load_ply(path, Arc::new(Lambertian::new(Color::new(0.9, 0.9, 0.9)))) {
+        Ok(mut sphere) => {
+            for p in &mut sphere.positions {
+                *p = Point3::new(-2.2, 1.3, 0.0) + *p * 1.3;
+            }
+            world
+                .objects
+                .extend(sphere.with_smooth_normals().triangles().objects);
+        }
+        Err(e) => eprintln!("{}: {}", path, e),
+    }
+    let path = "models/trefoil.stl";
+    match TriangleMesh::load_stl(path, Arc::new(Metal::new(Color::new(0.8, 0.6, 0.3), 0.1))) {
+        Ok(mut knot) => {
+            for p in &mut knot.positions {
+                *p = Point3::new(2.2, 1.5, 0.0) + *p * 0.45;
+            }
+            world
+                .objects
+                .extend(knot.with_smooth_normals().triangles().objects);
+        }
+        Err(e) => eprintln!("{}: {}", path, e),
+    }
+
+    Arc::new(Scene::new(&world, 0.0, 1.0))
 }
 
 fn main() {
@@ -120,6 +1210,7 @@ fn main() {
     let image_height = (image_width as f64 / aspect_ratio) as i32;
     let samples_per_pixel = 100;
     let max_depth = 50;
+    let sampler = SobolSampler::new(samples_per_pixel, 0);
 
     // World
 
@@ -154,13 +1245,140 @@ fn main() {
     //     material_right,
     // )));
 
-    let world = random_scene();
+    let scene = 1;
+    let world;
+    let lookfrom;
+    let lookat;
+    let mut aperture = 0.0;
+    // Distance to the plane in focus, the look-at point unless set.
+    let mut disk_to_focus = None;
+    let mut spectral = false;
+    // Outputs besides test.png: a denoised copy, extra passes written next to
+    // it and to test.exr, and snapshots of the render in progress.
+    let mut denoise_output = false;
+    let mut aovs: Vec<Aov> = vec![];
+    let mut snapshot = None;
+    match scene {
+        1 => {
+            world = random_scene();
+            lookfrom = Point3::new(13.0, 2.0, 3.0);
+            lookat = Point3::default();
+            aperture = 0.1;
+            disk_to_focus = Some(10.0);
+            denoise_output = true;
+            aovs = vec![
+                Aov::Depth,
+                Aov::Normal,
+                Aov::Albedo,
+                Aov::ObjectId,
+                Aov::MaterialId,
+                Aov::Motion,
+            ];
+            snapshot = Some(Snapshot {
+                path: "progress.png".to_string(),
+                interval: Duration::from_secs(10),
+            });
+        }
+        2 => {
+            world = metals();
+            lookfrom = Point3::new(0.0, 2.0, 18.0);
+            lookat = Point3::default().set_y(0.8);
+        }
+        3 => {
+            world = principled_spheres();
+            lookfrom = Point3::new(0.0, 2.0, 18.0);
+            lookat = Point3::default().set_y(0.8);
+        }
+        4 => {
+            world = frosted_glass();
+            lookfrom = Point3::new(0.0, 2.0, 18.0);
+            lookat = Point3::default().set_y(0.8);
+        }
+        5 => {
+            world = coloured_glass();
+            lookfrom = Point3::new(0.0, 2.0, 18.0);
+            lookat = Point3::default().set_y(0.8);
+        }
+        6 => {
+            world = dispersion();
+            lookfrom = Point3::new(0.0, 2.0, 18.0);
+            lookat = Point3::default().set_y(1.2);
+            spectral = true;
+        }
+        7 => {
+            world = subsurface();
+            lookfrom = Point3::new(0.0, 2.0, 18.0);
+            lookat = Point3::default().set_y(0.8);
+        }
+        8 => {
+            world = thin_sheets();
+            lookfrom = Point3::new(0.0, 2.0, 18.0);
+            lookat = Point3::default().set_y(0.8);
+        }
+        9 => {
+            world = mapped_surfaces();
+            lookfrom = Point3::new(0.0, 2.0, 18.0);
+            lookat = Point3::default().set_y(0.8);
+        }
+        10 => {
+            world = layered_materials();
+            lookfrom = Point3::new(0.0, 2.0, 18.0);
+            lookat = Point3::default().set_y(0.8);
+        }
+        11 => {
+            world = measured_brdfs();
+            lookfrom = Point3::new(0.0, 2.0, 18.0);
+            lookat = Point3::default().set_y(0.8);
+        }
+        12 => {
+            world = analytic_shapes();
+            lookfrom = Point3::new(0.0, 2.0, 18.0);
+            lookat = Point3::default().set_y(0.8);
+        }
+        13 => {
+            world = csg_solids();
+            lookfrom = Point3::new(0.0, 2.0, 18.0);
+            lookat = Point3::default().set_y(0.8);
+        }
+        14 => {
+            world = distance_fields();
+            lookfrom = Point3::new(0.0, 2.0, 18.0);
+            lookat = Point3::default().set_y(0.8);
+        }
+        15 => {
+            world = quadrics_and_implicits();
+            lookfrom = Point3::new(0.0, 2.0, 18.0);
+            lookat = Point3::default().set_y(0.8);
+        }
+        16 => {
+            world = patches_and_subdivision();
+            lookfrom = Point3::new(0.0, 2.0, 18.0);
+            lookat = Point3::default().set_y(0.8);
+        }
+        17 => {
+            world = hair_and_grass();
+            lookfrom = Point3::new(0.0, 2.0, 18.0);
+            lookat = Point3::default().set_y(0.8);
+        }
+        18 => {
+            world = terrain();
+            lookfrom = Point3::new(0.0, 2.0, 18.0);
+            lookat = Point3::default().set_y(0.8);
+        }
+        19 => {
+            world = particles();
+            lookfrom = Point3::new(0.0, 2.0, 18.0);
+            lookat = Point3::default().set_y(0.8);
+        }
+        _ => {
+            world = scanned_meshes();
+            lookfrom = Point3::new(0.0, 2.0, 18.0);
+            lookat = Point3::default().set_y(0.8);
+        }
+    }
     // Camera
-    let lookfrom = Point3::new(13.0, 2.0, 3.0);
-    let lookat = Point3::default();
     let vup = Vec3::default().set_y(1.0);
-    let disk_to_focus = 10.0;
-    let aperture = 0.1;
+    let disk_to_focus = disk_to_focus.unwrap_or_else(|| (lookfrom - lookat).length());
     let cam = Camera::new(
         lookfrom,
         lookat,
@@ -172,58 +1390,36 @@ fn main() {
     )
     .set_time(0.0, 1.0);
     //Render
-    let mut img = RgbImage::new(image_width as u32, image_height as u32);
-    println!("P3\n{} {}\n255\n", image_width, image_height);
-    for j in 0..image_height {
-        let j = image_height - j;
-        eprintln!("\rScanlines remaining: {} ", j);
-        for i in 0..image_width {
-            let pixel_color = (0..samples_per_pixel)
-                .into_par_iter()
-                .map(|_| {
-                    let u = (i as f64 + rand_01()) / (image_width as f64 - 1.0);
-                    let v = (j as f64 + rand_01()) / (image_height as f64 - 1.0);
-                    let r = cam.get_ray(u, v);
-                    ray_color(&r, world.clone(), max_depth)
-                })
-                .collect::<Vec<Color>>()
-                .iter()
-                .fold(Color::default(), |a: Color, b: &Color| a + *b);
-
-            write_color_to_png(
-                &mut img,
-                i as u32,
-                (image_height - j) as u32,
-                pixel_color,
-                samples_per_pixel,
-            );
-        }
+    let settings = RenderSettings {
+        samples_per_pixel,
+        max_depth,
+        tile_size: 16,
+        samples_per_pass: 10,
+        snapshot,
+        spectral,
+    };
+    let filter = Arc::new(MitchellFilter::new(2.0, 1.0 / 3.0, 1.0 / 3.0));
+    let mut film = Film::new(image_width as u32, image_height as u32, filter);
+    render(&world, &cam, &settings, &sampler, &mut film);
+    film.to_image().save("test.png").unwrap();
+    if !aovs.is_empty() {
+        write_aov_images(&film, &aovs, "test").unwrap();
+        let mut layers = vec![Aov::Beauty];
+        layers.extend(&aovs);
+        write_exr_layers(&film, &layers, "test.exr").unwrap();
+    }
+    if denoise_output {
+        let denoised = denoise(
+            film.width,
+            film.height,
+            &film.color_buffer(),
+            &film.albedo_buffer(),
+            &film.normal_buffer(),
+            &DenoiseSettings::default(),
+        );
+        buffer_to_image(film.width, film.height, &denoised)
+            .save("test_denoised.png")
+            .unwrap();
     }
-    img.save("test.png").unwrap();
     eprintln!("\nDone.\n");
 }
-
-pub fn write_color_to_png(
-    img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>,
-    x: u32,
-    y: u32,
-    pixel_color: Color,
-    samples_per_pixel: usize,
-) {
-    let mut r = pixel_color.x;
-    let mut g = pixel_color.y;
-    let mut b = pixel_color.z;
-    // Divide the color by the number of pixels
-    let scale = 1.0 / samples_per_pixel as f64;
-    // gamma-correct for gamma =2.0
-    r = (scale * r).sqrt();
-    g = (scale * g).sqrt();
-    b = (scale * b).sqrt();
-
-    // write the translated [0,255] value of each color component
-    let ir = (256.0 * clamp(r, 0.0, 0.999)) as u8;
-    let ig = (256.0 * clamp(g, 0.0, 0.999)) as u8;
-    let ib = (256.0 * clamp(b, 0.0, 0.999)) as u8;
-
-    img.put_pixel(x, y, Rgb([ir, ig, ib]));
-}
@@ -1,8 +1,13 @@
 use crate::{
     hittable::HitRecord,
+    medium::Medium,
+    microfacet::{fresnel_complex, fresnel_dielectric, TrowbridgeReitz},
+    onb::Onb,
     ray::Ray,
-    rtweekend::rand_01,
-    vec3::{dot, random_in_unit_sphere, random_unit_vector, reflect, refract, Color},
+    rtweekend::PI,
+    sampler::{sample_cosine_hemisphere, sample_unit_ball, sample_unit_sphere, Sampler},
+    spectrum::Dispersion,
+    vec3::{dot, reflect, refract, Color, Vec3},
 };
 
 pub trait Material {
@@ -12,7 +17,38 @@ pub trait Material {
         rec: &HitRecord,
         attenuation: &mut Color,
         scattered: &mut Ray,
+        sampler: &mut dyn Sampler,
     ) -> bool;
+
+    // BSDF times |cos theta| for light arriving along `scattered` and leaving
+    // against `r_in`. None for materials with delta lobes or without a closed
+    // form, which can only be sampled.
+    fn eval(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> Option<Color> {
+        None
+    }
+
+    // Solid angle density with which `scatter` picks `scattered`. Only
+    // meaningful where `eval` returns Some.
+    fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f64 {
+        0.0
+    }
+
+    // Surface colour seen by feature buffers and AOVs.
+    fn albedo(&self, _rec: &HitRecord) -> Color {
+        Color::new(1.0, 1.0, 1.0)
+    }
+
+    // The medium enclosed by surfaces of this material, if light can enter it,
+    // as seen at the hero wavelength of a spectral path.
+    fn medium(&self, _wavelength: Option<f64>) -> Option<Medium> {
+        None
+    }
+
+    // Whether scattering depends on wavelength, so a spectral path can only
+    // carry on at its hero wavelength.
+    fn dispersive(&self) -> bool {
+        false
+    }
 }
 
 pub struct Lambertian {
@@ -32,8 +68,9 @@ impl Material for Lambertian {
         rec: &HitRecord,
         attenuation: &mut Color,
         scattered: &mut Ray,
+        sampler: &mut dyn Sampler,
     ) -> bool {
-        let mut scatter_direction = rec.normal + random_unit_vector();
+        let mut scatter_direction = rec.normal + sample_unit_sphere(sampler.get_2d());
 
         // Catch degenerate scatter direction
         if scatter_direction.near_zero() {
@@ -44,6 +81,19 @@ impl Material for Lambertian {
         *attenuation = self.albedo;
         true
     }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Option<Color> {
+        Some(self.albedo * self.scattering_pdf(r_in, rec, scattered))
+    }
+
+    fn scattering_pdf(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let cosine = dot(&rec.normal, &scattered.direction().unit_vector());
+        cosine.max(0.0) / PI
+    }
+
+    fn albedo(&self, _rec: &HitRecord) -> Color {
+        self.albedo
+    }
 }
 
 pub struct Metal {
@@ -73,28 +123,284 @@ impl Material for Metal {
         rec: &HitRecord,
         attenuation: &mut Color,
         scattered: &mut Ray,
+        sampler: &mut dyn Sampler,
     ) -> bool {
         let reflected = reflect(r_in.direction().unit_vector(), rec.normal);
         *scattered = Ray::new(
             rec.p,
-            reflected + random_in_unit_sphere() * self.fuzz,
+            reflected + sample_unit_ball(sampler.get_2d(), sampler.get_1d()) * self.fuzz,
             r_in.time(),
         );
         *attenuation = self.albedo;
         dot(scattered.direction(), &rec.normal) > 0.0
     }
+
+    fn albedo(&self, _rec: &HitRecord) -> Color {
+        self.albedo
+    }
+}
+
+// Rough conductor: GGX microfacets with Smith height-correlated masking-shadowing
+// and the exact Fresnel term for a complex index of refraction eta + ik, given
+// per RGB channel. Directions are drawn from the visible normal distribution.
+pub struct Microfacet {
+    pub eta: Color,
+    pub k: Color,
+    pub distribution: TrowbridgeReitz,
+}
+
+impl Microfacet {
+    pub fn new(eta: Color, k: Color, roughness: f64) -> Microfacet {
+        let alpha = TrowbridgeReitz::roughness_to_alpha(roughness);
+        Microfacet {
+            eta,
+            k,
+            distribution: TrowbridgeReitz::new(alpha, alpha),
+        }
+    }
+
+    // Measured optical constants sampled at roughly 650, 550 and 450 nm.
+    pub fn gold(roughness: f64) -> Microfacet {
+        Microfacet::new(
+            Color::new(0.143, 0.374, 1.442),
+            Color::new(3.983, 2.386, 1.603),
+            roughness,
+        )
+    }
+
+    pub fn copper(roughness: f64) -> Microfacet {
+        Microfacet::new(
+            Color::new(0.200, 0.924, 1.102),
+            Color::new(3.912, 2.452, 2.142),
+            roughness,
+        )
+    }
+
+    pub fn aluminium(roughness: f64) -> Microfacet {
+        Microfacet::new(
+            Color::new(1.657, 0.880, 0.521),
+            Color::new(9.224, 6.270, 4.837),
+            roughness,
+        )
+    }
+
+    pub fn silver(roughness: f64) -> Microfacet {
+        Microfacet::new(
+            Color::new(0.155, 0.117, 0.138),
+            Color::new(4.828, 3.122, 2.147),
+            roughness,
+        )
+    }
+
+    pub fn iron(roughness: f64) -> Microfacet {
+        Microfacet::new(
+            Color::new(2.911, 2.950, 2.584),
+            Color::new(3.089, 2.932, 2.767),
+            roughness,
+        )
+    }
+
+    pub fn fresnel(&self, cos_theta: f64) -> Color {
+        Color::new(
+            fresnel_complex(cos_theta, self.eta.x, self.k.x),
+            fresnel_complex(cos_theta, self.eta.y, self.k.y),
+            fresnel_complex(cos_theta, self.eta.z, self.k.z),
+        )
+    }
 }
 
+impl Material for Microfacet {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
+        let frame = Onb::build_from_w(&rec.normal);
+        let wo = frame.to_local(-r_in.direction().unit_vector());
+        let u = sampler.get_2d();
+        if wo.z <= 0.0 {
+            return false;
+        }
+
+        if self.distribution.effectively_smooth() {
+            *scattered = Ray::new(
+                rec.p,
+                frame.local(Vec3::new(-wo.x, -wo.y, wo.z)),
+                r_in.time(),
+            );
+            *attenuation = self.fresnel(wo.z);
+            return true;
+        }
+
+        let wm = self.distribution.sample_wm(wo, u);
+        let wi = reflect(-wo, wm);
+        if wi.z <= 0.0 {
+            return false;
+        }
+        // f * cos / pdf for visible normal sampling reduces to F * G2 / G1.
+        *attenuation = self.fresnel(dot(&wo, &wm))
+            * (self.distribution.g(wo, wi) / self.distribution.g1(wo));
+        *scattered = Ray::new(rec.p, frame.local(wi), r_in.time());
+        true
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Option<Color> {
+        if self.distribution.effectively_smooth() {
+            return None;
+        }
+        let (wo, wi) = local_directions(r_in, rec, scattered);
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return Some(Color::default());
+        }
+        let wm = (wo + wi).unit_vector();
+        let d = self.distribution.d(wm) * self.distribution.g(wo, wi) / (4.0 * wo.z);
+        Some(self.fresnel(dot(&wo, &wm)) * d)
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let (wo, wi) = local_directions(r_in, rec, scattered);
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return 0.0;
+        }
+        let wm = (wo + wi).unit_vector();
+        self.distribution.d_visible(wo, wm) / (4.0 * dot(&wo, &wm))
+    }
+
+    fn albedo(&self, _rec: &HitRecord) -> Color {
+        self.fresnel(1.0)
+    }
+}
+
+// Glass. With zero roughness the interface is perfectly smooth; rough glass
+// uses the microfacet BTDF of Walter et al. 2007 with GGX normals sampled from
+// the visible distribution and the exact dielectric Fresnel term. The interior
+// absorbs light per unit distance travelled according to `absorption`.
 pub struct Dielectric {
     pub ir: f64,
+    pub distribution: TrowbridgeReitz,
+    pub absorption: Color,
+    pub priority: i32,
+    // Wavelength dependent index used by the spectral mode; `ir` is the index
+    // at the sodium d line, used when rendering in RGB.
+    pub dispersion: Option<Dispersion>,
 }
 
 impl Dielectric {
     pub fn new(index_of_refraction: f64) -> Dielectric {
+        Dielectric::rough(index_of_refraction, 0.0)
+    }
+
+    // Frosted or sandblasted glass; roughness in [0, 1].
+    pub fn rough(index_of_refraction: f64, roughness: f64) -> Dielectric {
+        let alpha = TrowbridgeReitz::roughness_to_alpha(roughness);
         Dielectric {
             ir: index_of_refraction,
+            distribution: TrowbridgeReitz::new(alpha, alpha),
+            absorption: Color::default(),
+            priority: 0,
+            dispersion: None,
         }
     }
+
+    pub fn with_dispersion(mut self, dispersion: Dispersion) -> Dielectric {
+        self.ir = dispersion.ior(587.6);
+        self.dispersion = Some(dispersion);
+        self
+    }
+
+    // Schott N-BK7 crown glass.
+    pub fn crown_glass() -> Dielectric {
+        Dielectric::new(1.5168).with_dispersion(Dispersion::Sellmeier {
+            b: [1.03961212, 0.231792344, 1.01046945],
+            c: [0.00600069867, 0.0200179144, 103.560653],
+        })
+    }
+
+    // Schott SF11 dense flint glass, strongly dispersive.
+    pub fn flint_glass() -> Dielectric {
+        Dielectric::new(1.7847).with_dispersion(Dispersion::Sellmeier {
+            b: [1.73759695, 0.313747346, 1.89878101],
+            c: [0.013188707, 0.0623068142, 155.23629],
+        })
+    }
+
+    pub fn diamond() -> Dielectric {
+        Dielectric::new(2.4175).with_dispersion(Dispersion::Sellmeier {
+            b: [0.3306, 4.3356, 0.0],
+            c: [0.030625, 0.011236, 0.0],
+        })
+    }
+
+    pub fn ior(&self, wavelength: Option<f64>) -> f64 {
+        match (self.dispersion, wavelength) {
+            (Some(dispersion), Some(lambda)) => dispersion.ior(lambda),
+            _ => self.ir,
+        }
+    }
+
+    // Absorption coefficient per unit distance for each channel.
+    pub fn with_absorption(mut self, absorption: Color) -> Dielectric {
+        self.absorption = absorption;
+        self
+    }
+
+    // Coloured glass that transmits `color` after `distance` units of travel.
+    pub fn with_tint(self, color: Color, distance: f64) -> Dielectric {
+        let sigma = |c: f64| -c.max(1e-6).ln() / distance;
+        self.with_absorption(Color::new(sigma(color.x), sigma(color.y), sigma(color.z)))
+    }
+
+    // Where volumes overlap the higher priority medium fills the overlap.
+    pub fn with_priority(mut self, priority: i32) -> Dielectric {
+        self.priority = priority;
+        self
+    }
+
+    fn scatter_rough(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
+        // Relative index n_transmitted / n_incident.
+        let eta = if rec.front_face {
+            self.ior(r_in.wavelength()) / rec.outside_ior
+        } else {
+            rec.outside_ior / self.ior(r_in.wavelength())
+        };
+        let frame = Onb::build_from_w(&rec.normal);
+        let wo = frame.to_local(-r_in.direction().unit_vector());
+        let u = sampler.get_1d();
+        let wm = self.distribution.sample_wm(wo, sampler.get_2d());
+        if wo.z <= 0.0 {
+            return false;
+        }
+
+        // Choosing reflection with probability F cancels the Fresnel factor, and
+        // visible normal sampling leaves G2 / G1 for either choice.
+        let wi = if u < fresnel_dielectric(dot(&wo, &wm), eta) {
+            let wi = reflect(-wo, wm);
+            if wi.z <= 0.0 {
+                return false;
+            }
+            wi
+        } else {
+            let wi = refract(-wo, wm, 1.0 / eta);
+            if wi.z >= 0.0 {
+                return false;
+            }
+            wi
+        };
+        *attenuation = Color::new(1.0, 1.0, 1.0)
+            * (self.distribution.g(wo, wi) / self.distribution.g1(wo));
+        *scattered = Ray::new(rec.p, frame.local(wi), r_in.time());
+        true
+    }
 }
 
 impl Material for Dielectric {
@@ -104,11 +410,16 @@ impl Material for Dielectric {
         rec: &HitRecord,
         attenuation: &mut Color,
         scattered: &mut Ray,
+        sampler: &mut dyn Sampler,
     ) -> bool {
+        if !self.distribution.effectively_smooth() {
+            return self.scatter_rough(r_in, rec, attenuation, scattered, sampler);
+        }
+
         let refraction_ratio = if rec.front_face {
-            1.0 / self.ir
+            rec.outside_ior / self.ior(r_in.wavelength())
         } else {
-            self.ir
+            self.ior(r_in.wavelength()) / rec.outside_ior
         };
 
         let unit_direction = r_in.direction().unit_vector();
@@ -121,8 +432,9 @@ impl Material for Dielectric {
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
 
         let cannot_reflect = refraction_ratio * sin_theta > 1.0;
+        let u = sampler.get_1d();
 
-        let direction = if cannot_reflect || reflectance(cos_theta, refraction_ratio) > rand_01() {
+        let direction = if cannot_reflect || reflectance(cos_theta, refraction_ratio) > u {
             reflect(unit_direction, rec.normal)
         } else {
             refract(unit_direction, rec.normal, refraction_ratio)
@@ -131,6 +443,222 @@ impl Material for Dielectric {
         *scattered = Ray::new(rec.p, direction, r_in.time());
         true
     }
+
+    fn medium(&self, wavelength: Option<f64>) -> Option<Medium> {
+        Some(Medium {
+            ior: self.ior(wavelength),
+            absorption: self.absorption,
+            scattering: Color::default(),
+            anisotropy: 0.0,
+            priority: self.priority,
+        })
+    }
+
+    fn dispersive(&self) -> bool {
+        self.dispersion.is_some()
+    }
+}
+
+// A dielectric sheet too thin for the offset of the refracted ray to matter,
+// such as a window pane or a soap bubble: light is either reflected or passes
+// straight through, with the sum of the inter-reflections inside the sheet
+// folded into the reflectance. Unlike `Dielectric` it does not need a closed
+// surface. The albedos tint light arriving at the front and back faces.
+pub struct ThinDielectric {
+    pub ir: f64,
+    pub front: Color,
+    pub back: Color,
+}
+
+impl ThinDielectric {
+    pub fn new(index_of_refraction: f64) -> ThinDielectric {
+        ThinDielectric {
+            ir: index_of_refraction,
+            front: Color::new(1.0, 1.0, 1.0),
+            back: Color::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    pub fn with_albedo(mut self, front: Color, back: Color) -> ThinDielectric {
+        self.front = front;
+        self.back = back;
+        self
+    }
+}
+
+impl Material for ThinDielectric {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
+        let unit_direction = r_in.direction().unit_vector();
+        let cos_theta = dot(&-unit_direction, &rec.normal).min(1.0);
+        let mut r = fresnel_dielectric(cos_theta, self.ir);
+        if r < 1.0 {
+            r += (1.0 - r) * (1.0 - r) * r / (1.0 - r * r);
+        }
+        let direction = if sampler.get_1d() < r {
+            reflect(unit_direction, rec.normal)
+        } else {
+            unit_direction
+        };
+        *attenuation = self.albedo(rec);
+        *scattered = Ray::new(rec.p, direction, r_in.time());
+        true
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        if rec.front_face {
+            self.front
+        } else {
+            self.back
+        }
+    }
+}
+
+// Translucent diffuse surface for leaves, paper and curtains: a fraction
+// `transmission` of the light scattered diffusely goes out of the other side.
+// `front` and `back` are the albedos of the two faces.
+pub struct DiffuseTransmitter {
+    pub front: Color,
+    pub back: Color,
+    pub transmission: f64,
+}
+
+impl DiffuseTransmitter {
+    pub fn new(front: Color, back: Color, transmission: f64) -> DiffuseTransmitter {
+        DiffuseTransmitter {
+            front,
+            back,
+            transmission: transmission.clamp(0.0, 1.0),
+        }
+    }
+}
+
+impl Material for DiffuseTransmitter {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
+        let transmit = sampler.get_1d() < self.transmission;
+        let mut local = sample_cosine_hemisphere(sampler.get_2d());
+        if transmit {
+            local.z = -local.z;
+        }
+        let direction = Onb::build_from_w(&rec.normal).local(local);
+        *attenuation = self.albedo(rec);
+        *scattered = Ray::new(rec.p, direction, r_in.time());
+        true
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Option<Color> {
+        Some(self.albedo(rec) * self.scattering_pdf(r_in, rec, scattered))
+    }
+
+    fn scattering_pdf(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let cosine = dot(&rec.normal, &scattered.direction().unit_vector());
+        let side = if cosine < 0.0 {
+            self.transmission
+        } else {
+            1.0 - self.transmission
+        };
+        side * cosine.abs() / PI
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        if rec.front_face {
+            self.front
+        } else {
+            self.back
+        }
+    }
+}
+
+// Random walk subsurface scattering for wax, marble and skin: a dielectric
+// boundary around a medium in which light scatters until it leaves the object
+// again. `albedo` is the single scattering albedo and `mean_free_path` the mean
+// distance between scattering events, per channel and in scene units. The walk
+// is carried out by the integrator, which follows the path through the surfaces
+// of the enclosing object like any other closed volume.
+pub struct Subsurface {
+    pub albedo: Color,
+    pub mean_free_path: Color,
+    // Henyey-Greenstein g; positive values scatter forwards, as in skin.
+    pub anisotropy: f64,
+    pub interface: Dielectric,
+}
+
+impl Subsurface {
+    pub fn new(albedo: Color, mean_free_path: Color, index_of_refraction: f64) -> Subsurface {
+        Subsurface {
+            albedo,
+            mean_free_path,
+            anisotropy: 0.0,
+            interface: Dielectric::new(index_of_refraction),
+        }
+    }
+
+    pub fn with_anisotropy(mut self, anisotropy: f64) -> Subsurface {
+        self.anisotropy = anisotropy;
+        self
+    }
+
+    // Boundary roughness in [0, 1], as for `Dielectric::rough`.
+    pub fn with_roughness(mut self, roughness: f64) -> Subsurface {
+        self.interface = Dielectric::rough(self.interface.ir, roughness);
+        self
+    }
+}
+
+impl Material for Subsurface {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
+        self.interface.scatter(r_in, rec, attenuation, scattered, sampler)
+    }
+
+    fn albedo(&self, _rec: &HitRecord) -> Color {
+        self.albedo
+    }
+
+    fn medium(&self, wavelength: Option<f64>) -> Option<Medium> {
+        let sigma_t = |mfp: f64| 1.0 / mfp.max(1e-6);
+        let extinction = Color::new(
+            sigma_t(self.mean_free_path.x),
+            sigma_t(self.mean_free_path.y),
+            sigma_t(self.mean_free_path.z),
+        );
+        Some(Medium {
+            ior: self.interface.ior(wavelength),
+            absorption: extinction * (Color::new(1.0, 1.0, 1.0) - self.albedo),
+            scattering: extinction * self.albedo,
+            anisotropy: self.anisotropy,
+            priority: self.interface.priority,
+        })
+    }
+}
+
+// The directions of a scattering event in the shading frame of `rec`: towards
+// the viewer and towards the light.
+pub(crate) fn local_directions(r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> (Vec3, Vec3) {
+    let frame = Onb::build_from_w(&rec.normal);
+    (
+        frame.to_local(-r_in.direction().unit_vector()),
+        frame.to_local(scattered.direction().unit_vector()),
+    )
 }
 
 pub fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
@@ -0,0 +1,216 @@
+use std::f64::consts::PI;
+
+use crate::{
+    onb::Onb,
+    rtweekend::INFINITY,
+    sampler::Sampler,
+    vec3::{Color, Vec3},
+};
+
+// The inside of a closed surface whose material lets light through. Light
+// travelling a distance d inside is attenuated by exp(-absorption * d)
+// (Beer-Lambert). Where closed surfaces overlap, the medium with the higher
+// priority wins, so liquid in a glass is modelled as a liquid volume that
+// slightly overlaps the inner glass wall with a lower priority than the glass
+// (Schmidt and Budge 2002, "Simple Nested Dielectrics in Ray Traced Images").
+// A medium with a scattering coefficient is also scattered through, with a
+// Henyey-Greenstein phase function of the given anisotropy.
+#[derive(Debug, Clone, Copy)]
+pub struct Medium {
+    pub ior: f64,
+    pub absorption: Color,
+    pub scattering: Color,
+    pub anisotropy: f64,
+    pub priority: i32,
+}
+
+impl Medium {
+    pub fn extinction(&self) -> Color {
+        self.absorption + self.scattering
+    }
+
+    pub fn is_scattering(&self) -> bool {
+        self.scattering.x > 0.0 || self.scattering.y > 0.0 || self.scattering.z > 0.0
+    }
+
+    // A new direction for light travelling along `direction` (unit length) that
+    // scatters in the medium. The phase function is sampled exactly, so the
+    // throughput weight is one.
+    pub fn sample_phase(&self, direction: Vec3, u: (f64, f64)) -> Vec3 {
+        let g = self.anisotropy;
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * u.0
+        } else {
+            let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * u.0);
+            (1.0 + g * g - s * s) / (2.0 * g)
+        };
+        let sin_theta = f64::max(0.0, 1.0 - cos_theta * cos_theta).sqrt();
+        let phi = 2.0 * PI * u.1;
+        Onb::build_from_w(&direction).local(Vec3::new(
+            sin_theta * phi.cos(),
+            sin_theta * phi.sin(),
+            cos_theta,
+        ))
+    }
+}
+
+// Outcome of following a path segment through the current medium.
+#[derive(Debug, Clone, Copy)]
+pub enum Interaction {
+    // Light scatters into `direction` after `distance`; `weight` is the
+    // throughput up to there.
+    Scatter {
+        distance: f64,
+        direction: Vec3,
+        weight: Color,
+    },
+    // The segment reaches its end with throughput `weight`.
+    Pass {
+        weight: Color,
+    },
+}
+
+// The media a path is currently inside, in the order it entered them. Each
+// entry is keyed by the id of the material that bounds it.
+#[derive(Debug, Clone, Default)]
+pub struct MediumStack {
+    entries: Vec<(u32, Medium)>,
+    // Random walk state: the channel whose extinction drives distance sampling
+    // for the current walk, and each channel's pdf of the walk so far relative
+    // to their mean.
+    walk_channel: Option<i32>,
+    walk_pdf: Color,
+}
+
+impl MediumStack {
+    // The medium that governs the path, the highest priority one; ties go to the
+    // one entered last. None means vacuum.
+    pub fn current(&self) -> Option<&Medium> {
+        highest(self.entries.iter())
+    }
+
+    // Fraction of light that survives `distance` in the current medium.
+    pub fn transmittance(&self, distance: f64) -> Color {
+        match self.current() {
+            Some(m) => beer_lambert(m.extinction(), distance),
+            None => Color::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    // Follows a segment of length `distance` (possibly infinite) along the unit
+    // vector `direction` through the current medium. In a scattering medium,
+    // free-flight distances for a whole walk are sampled with the extinction of
+    // one channel chosen at random, and weighted by the mean over channels of
+    // the pdf of the walk so far (one-sample MIS), which keeps coloured media
+    // unbiased without the variance of choosing a new channel at every step.
+    pub fn sample_interaction(
+        &mut self,
+        direction: Vec3,
+        distance: f64,
+        sampler: &mut dyn Sampler,
+    ) -> Interaction {
+        let m = match self.current() {
+            Some(m) if m.is_scattering() => *m,
+            _ => {
+                return Interaction::Pass {
+                    weight: self.transmittance(distance),
+                }
+            }
+        };
+        let channel = match self.walk_channel {
+            Some(channel) => channel,
+            None => {
+                let channel = ((sampler.get_1d() * 3.0) as i32).min(2);
+                self.walk_channel = Some(channel);
+                self.walk_pdf = Color::new(1.0, 1.0, 1.0);
+                channel
+            }
+        };
+        let sigma_t = m.extinction();
+        let t = -(1.0 - sampler.get_1d()).ln() / sigma_t[channel];
+        if t < distance {
+            let tr = beer_lambert(sigma_t, t);
+            Interaction::Scatter {
+                distance: t,
+                direction: m.sample_phase(direction, sampler.get_2d()),
+                weight: tr * m.scattering / self.update_walk_pdf(sigma_t * tr),
+            }
+        } else {
+            let tr = beer_lambert(sigma_t, distance);
+            Interaction::Pass {
+                weight: tr / self.update_walk_pdf(tr),
+            }
+        }
+    }
+
+    // Extends the walk by a step with per-channel pdf `pdf` and returns the MIS
+    // denominator for the step.
+    fn update_walk_pdf(&mut self, pdf: Color) -> f64 {
+        let walk_pdf = self.walk_pdf * pdf;
+        let norm = mean(walk_pdf);
+        if norm > 0.0 {
+            self.walk_pdf = walk_pdf / norm;
+            norm
+        } else {
+            INFINITY
+        }
+    }
+
+    // Whether a hit on the boundary of `medium` (material `id`) is a real optical
+    // interface. Returns the index of refraction on the far side of the
+    // boundary, or None if a higher priority medium hides the boundary and the
+    // path should carry straight on.
+    pub fn interface(&self, id: u32, medium: &Medium, entering: bool) -> Option<f64> {
+        let outside = if entering {
+            self.current()
+        } else {
+            let position = self.entries.iter().rposition(|(e, _)| *e == id);
+            highest(
+                self.entries
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| Some(*i) != position)
+                    .map(|(_, e)| e),
+            )
+        };
+        match outside {
+            Some(m) if m.priority > medium.priority => None,
+            Some(m) => Some(m.ior),
+            None => Some(1.0),
+        }
+    }
+
+    // Records that the path crossed the boundary of `medium`.
+    pub fn cross(&mut self, id: u32, medium: Medium, entering: bool) {
+        self.walk_channel = None;
+        if entering {
+            self.entries.push((id, medium));
+        } else if let Some(position) = self.entries.iter().rposition(|(e, _)| *e == id) {
+            self.entries.remove(position);
+        }
+    }
+}
+
+// exp(-sigma * distance) per channel. A channel that doesn't attenuate
+// transmits everything even over an infinite distance.
+fn beer_lambert(sigma: Color, distance: f64) -> Color {
+    let tr = |sigma: f64| {
+        if sigma > 0.0 {
+            (-sigma * distance).exp()
+        } else {
+            1.0
+        }
+    };
+    Color::new(tr(sigma.x), tr(sigma.y), tr(sigma.z))
+}
+
+fn mean(c: Color) -> f64 {
+    (c.x + c.y + c.z) / 3.0
+}
+
+fn highest<'a>(entries: impl Iterator<Item = &'a (u32, Medium)>) -> Option<&'a Medium> {
+    entries.fold(None, |best: Option<&Medium>, (_, m)| match best {
+        Some(b) if b.priority > m.priority => Some(b),
+        _ => Some(m),
+    })
+}
@@ -0,0 +1,337 @@
+use std::{
+    fs, io,
+    io::{Error, ErrorKind},
+};
+
+use crate::{
+    hittable::HitRecord,
+    material::{local_directions, Material},
+    onb::Onb,
+    ray::Ray,
+    rtweekend::PI,
+    sampler::{sample_cosine_hemisphere, Sampler},
+    vec3::{cross, dot, Color, Vec3},
+};
+
+// Resolution of the MERL tables in theta_half, theta_diff and phi_diff.
+const THETA_H: usize = 90;
+const THETA_D: usize = 90;
+const PHI_D: usize = 180;
+const TABLE_SIZE: usize = THETA_H * THETA_D * PHI_D;
+const SCALE: [f64; 3] = [1.0 / 1500.0, 1.15 / 1500.0, 1.66 / 1500.0];
+
+// Resolution of the tables used for importance sampling: bins of outgoing
+// elevation, and a grid of incoming elevation and azimuth relative to the
+// outgoing direction within each bin.
+const SAMPLE_THETA_O: usize = 16;
+const SAMPLE_THETA_I: usize = 32;
+const SAMPLE_PHI_I: usize = 64;
+// Share of samples drawn from the cosine distribution, so directions the
+// tables miss still have a density.
+const COSINE_FRACTION: f64 = 0.1;
+
+// Isotropic BRDF measured by the MERL database (Matusik et al. 2003), read
+// from the database's binary files and looked up in Rusinkiewicz's half and
+// difference angle parameterisation. Directions are importance sampled from
+// tables of the BRDF times cosine, mixed with some cosine sampling.
+pub struct Merl {
+    data: Vec<f64>,
+    sampling: Vec<Distribution2D>,
+}
+
+impl Merl {
+    pub fn load(path: &str) -> io::Result<Merl> {
+        let bytes = fs::read(path)?;
+        if bytes.len() < 12 {
+            return Err(invalid("truncated header"));
+        }
+        let dims = bytes[..12]
+            .chunks_exact(4)
+            .map(|b| usize::try_from(i32::from_le_bytes([b[0], b[1], b[2], b[3]])))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| invalid("negative table dimensions"))?;
+        let size = dims[0]
+            .checked_mul(dims[1])
+            .and_then(|n| n.checked_mul(dims[2]));
+        if size != Some(TABLE_SIZE) {
+            return Err(invalid("unexpected table dimensions"));
+        }
+        if bytes.len() != 12 + 3 * TABLE_SIZE * 8 {
+            return Err(invalid("unexpected file size"));
+        }
+        let data = bytes[12..]
+            .chunks_exact(8)
+            .map(|b| f64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+            .collect();
+        Merl::new(data)
+    }
+
+    // From the three colour planes of a MERL table, unscaled, as stored in the
+    // files.
+    pub fn new(data: Vec<f64>) -> io::Result<Merl> {
+        if data.len() != 3 * TABLE_SIZE {
+            return Err(invalid("MERL tables hold 3 x 90 x 90 x 180 values"));
+        }
+        let mut merl = Merl {
+            data,
+            sampling: Vec::new(),
+        };
+        merl.sampling = (0..SAMPLE_THETA_O)
+            .map(|bin| {
+                let theta_o = (bin as f64 + 0.5) / SAMPLE_THETA_O as f64 * PI / 2.0;
+                let wo = Vec3::new(theta_o.sin(), 0.0, theta_o.cos());
+                let mut values = Vec::with_capacity(SAMPLE_THETA_I * SAMPLE_PHI_I);
+                for i in 0..SAMPLE_THETA_I {
+                    for j in 0..SAMPLE_PHI_I {
+                        let (wi, area) = cell_center(i, j);
+                        values.push(luminance(merl.brdf(wi, wo)) * wi.z * area);
+                    }
+                }
+                Distribution2D::new(&values, SAMPLE_THETA_I, SAMPLE_PHI_I)
+            })
+            .collect();
+        Ok(merl)
+    }
+
+    // BRDF for local directions with the normal along +z.
+    pub fn brdf(&self, wi: Vec3, wo: Vec3) -> Color {
+        if wi.z <= 0.0 || wo.z <= 0.0 {
+            return Color::default();
+        }
+        let (theta_half, theta_diff, phi_diff) = half_diff_angles(wi, wo);
+        let index = phi_diff_index(phi_diff)
+            + theta_diff_index(theta_diff) * PHI_D
+            + theta_half_index(theta_half) * PHI_D * THETA_D;
+        let channel = |c: usize| (self.data[index + c * TABLE_SIZE] * SCALE[c]).max(0.0);
+        Color::new(channel(0), channel(1), channel(2))
+    }
+
+    // The sampling table for outgoing direction `wo`, and the rotation taking
+    // table directions to the azimuth of `wo`.
+    fn table(&self, wo: Vec3) -> (&Distribution2D, f64, f64) {
+        let theta_o = wo.z.clamp(-1.0, 1.0).acos();
+        let bin = ((theta_o / (PI / 2.0) * SAMPLE_THETA_O as f64) as usize).min(SAMPLE_THETA_O - 1);
+        let phi_o = wo.y.atan2(wo.x);
+        (&self.sampling[bin], phi_o.cos(), phi_o.sin())
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3) -> f64 {
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return 0.0;
+        }
+        let cosine = wi.z / PI;
+        let (table, cos_phi, sin_phi) = self.table(wo);
+        if table.total <= 0.0 {
+            return cosine;
+        }
+        // Rotate wi by -phi_o into the table's frame.
+        let x = wi.x * cos_phi + wi.y * sin_phi;
+        let y = -wi.x * sin_phi + wi.y * cos_phi;
+        let theta = wi.z.clamp(-1.0, 1.0).acos();
+        let phi = y.atan2(x).rem_euclid(2.0 * PI);
+        let i = ((theta / (PI / 2.0) * SAMPLE_THETA_I as f64) as usize).min(SAMPLE_THETA_I - 1);
+        let j = ((phi / (2.0 * PI) * SAMPLE_PHI_I as f64) as usize).min(SAMPLE_PHI_I - 1);
+        let (_, area) = cell_center(i, j);
+        let tabulated = table.probability(i, j) / area;
+        (1.0 - COSINE_FRACTION) * tabulated + COSINE_FRACTION * cosine
+    }
+
+    fn sample(&self, wo: Vec3, u_choice: f64, u: (f64, f64)) -> Vec3 {
+        let (table, cos_phi, sin_phi) = self.table(wo);
+        if table.total <= 0.0 || u_choice < COSINE_FRACTION {
+            return sample_cosine_hemisphere(u);
+        }
+        let (i, j, (ut, up)) = table.sample(u);
+        // Uniform in solid angle within the cell.
+        let (cos0, cos1) = cell_cos_bounds(i);
+        let cos_theta = cos0 + (cos1 - cos0) * ut;
+        let sin_theta = f64::max(0.0, 1.0 - cos_theta * cos_theta).sqrt();
+        let phi = (j as f64 + up) / SAMPLE_PHI_I as f64 * 2.0 * PI;
+        let (x, y) = (sin_theta * phi.cos(), sin_theta * phi.sin());
+        Vec3::new(
+            x * cos_phi - y * sin_phi,
+            x * sin_phi + y * cos_phi,
+            cos_theta,
+        )
+    }
+}
+
+impl Material for Merl {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
+        let frame = Onb::build_from_w(&rec.normal);
+        let wo = frame.to_local(-r_in.direction().unit_vector());
+        let u_choice = sampler.get_1d();
+        let u = sampler.get_2d();
+        if wo.z <= 0.0 {
+            return false;
+        }
+        let wi = self.sample(wo, u_choice, u);
+        let pdf = self.pdf(wo, wi);
+        if wi.z <= 0.0 || pdf <= 0.0 {
+            return false;
+        }
+        *attenuation = self.brdf(wi, wo) * (wi.z / pdf);
+        *scattered = Ray::new(rec.p, frame.local(wi), r_in.time());
+        true
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Option<Color> {
+        let (wo, wi) = local_directions(r_in, rec, scattered);
+        Some(self.brdf(wi, wo) * wi.z.max(0.0))
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let (wo, wi) = local_directions(r_in, rec, scattered);
+        self.pdf(wo, wi)
+    }
+
+    fn albedo(&self, _rec: &HitRecord) -> Color {
+        // Reflectance towards the normal, a reasonable base colour.
+        let n = Vec3::new(0.0, 0.0, 1.0);
+        self.brdf(n, n) * PI
+    }
+}
+
+// Rusinkiewicz coordinates (theta_half, theta_diff, phi_diff) of a pair of
+// local directions.
+fn half_diff_angles(wi: Vec3, wo: Vec3) -> (f64, f64, f64) {
+    let half = (wi + wo).unit_vector();
+    let theta_half = half.z.clamp(-1.0, 1.0).acos();
+    let phi_half = half.y.atan2(half.x);
+    let normal = Vec3::new(0.0, 0.0, 1.0);
+    let binormal = Vec3::new(0.0, 1.0, 0.0);
+    let diff = rotate(rotate(wi, normal, -phi_half), binormal, -theta_half);
+    let theta_diff = diff.z.clamp(-1.0, 1.0).acos();
+    let phi_diff = diff.y.atan2(diff.x);
+    (theta_half, theta_diff, phi_diff)
+}
+
+fn rotate(v: Vec3, axis: Vec3, angle: f64) -> Vec3 {
+    let (sin, cos) = angle.sin_cos();
+    v * cos + axis * (dot(&axis, &v) * (1.0 - cos)) + cross(&axis, &v) * sin
+}
+
+// theta_half is sampled more densely near the specular peak.
+fn theta_half_index(theta_half: f64) -> usize {
+    if theta_half <= 0.0 {
+        return 0;
+    }
+    let index = (theta_half / (PI / 2.0)).sqrt() * THETA_H as f64;
+    (index as usize).min(THETA_H - 1)
+}
+
+fn theta_diff_index(theta_diff: f64) -> usize {
+    let index = theta_diff / (PI / 2.0) * THETA_D as f64;
+    (index.max(0.0) as usize).min(THETA_D - 1)
+}
+
+// Reciprocity makes the table symmetric in phi_diff and phi_diff + pi.
+fn phi_diff_index(phi_diff: f64) -> usize {
+    let phi_diff = if phi_diff < 0.0 {
+        phi_diff + PI
+    } else {
+        phi_diff
+    };
+    let index = phi_diff / PI * PHI_D as f64;
+    (index.max(0.0) as usize).min(PHI_D - 1)
+}
+
+fn luminance(c: Color) -> f64 {
+    0.2126 * c.x + 0.7152 * c.y + 0.0722 * c.z
+}
+
+// cos(theta) at the top and bottom of row i of the sampling grid.
+fn cell_cos_bounds(i: usize) -> (f64, f64) {
+    let step = PI / 2.0 / SAMPLE_THETA_I as f64;
+    ((i as f64 * step).cos(), ((i + 1) as f64 * step).cos())
+}
+
+// Centre direction and solid angle of a cell of the sampling grid.
+fn cell_center(i: usize, j: usize) -> (Vec3, f64) {
+    let theta = (i as f64 + 0.5) / SAMPLE_THETA_I as f64 * PI / 2.0;
+    let phi = (j as f64 + 0.5) / SAMPLE_PHI_I as f64 * 2.0 * PI;
+    let (cos0, cos1) = cell_cos_bounds(i);
+    let area = (cos0 - cos1) * 2.0 * PI / SAMPLE_PHI_I as f64;
+    (
+        Vec3::new(
+            theta.sin() * phi.cos(),
+            theta.sin() * phi.sin(),
+            theta.cos(),
+        ),
+        area,
+    )
+}
+
+// Piecewise constant distribution over a rows x cols grid, sampled by
+// inverting the marginal CDF of the rows and then the CDF within a row.
+struct Distribution2D {
+    cols: usize,
+    values: Vec<f64>,
+    row_cdf: Vec<f64>,
+    cdfs: Vec<f64>,
+    total: f64,
+}
+
+impl Distribution2D {
+    fn new(values: &[f64], rows: usize, cols: usize) -> Distribution2D {
+        let mut cdfs = Vec::with_capacity(rows * (cols + 1));
+        let mut row_cdf = vec![0.0];
+        for row in values.chunks_exact(cols) {
+            let mut sum = 0.0;
+            cdfs.push(0.0);
+            for v in row {
+                sum += v;
+                cdfs.push(sum);
+            }
+            row_cdf.push(row_cdf.last().unwrap() + sum);
+        }
+        Distribution2D {
+            cols,
+            values: values.to_vec(),
+            total: *row_cdf.last().unwrap(),
+            row_cdf,
+            cdfs,
+        }
+    }
+
+    fn probability(&self, i: usize, j: usize) -> f64 {
+        self.values[i * self.cols + j] / self.total
+    }
+
+    // Cell (i, j) and the position within it, each in [0, 1).
+    fn sample(&self, u: (f64, f64)) -> (usize, usize, (f64, f64)) {
+        let (i, ui) = invert(&self.row_cdf, u.0);
+        let row = &self.cdfs[i * (self.cols + 1)..(i + 1) * (self.cols + 1)];
+        let (j, uj) = invert(row, u.1);
+        (i, j, (ui, uj))
+    }
+}
+
+// Finds the bucket of an unnormalised CDF that u in [0, 1) falls in, and where
+// in the bucket.
+fn invert(cdf: &[f64], u: f64) -> (usize, f64) {
+    let target = u * cdf[cdf.len() - 1];
+    let k = cdf
+        .partition_point(|&c| c <= target)
+        .clamp(1, cdf.len() - 1)
+        - 1;
+    // Skip empty buckets that share their upper bound with the target.
+    let width = cdf[k + 1] - cdf[k];
+    let offset = if width > 0.0 {
+        ((target - cdf[k]) / width).clamp(0.0, 1.0 - f64::EPSILON)
+    } else {
+        0.5
+    };
+    (k, offset)
+}
+
+fn invalid(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg.to_string())
+}
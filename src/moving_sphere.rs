@@ -2,8 +2,9 @@ use std::sync::Arc;
 
 use crate::{
     aabb::*,
-    hittable::Hittable,
+    hittable::{material_id, new_object_id, Hittable},
     material::Material,
+    sphere::{get_sphere_tangents, get_sphere_uv},
     vec3::{dot, Point3, Vec3},
 };
 
@@ -14,6 +15,8 @@ pub struct MovingSphere {
     pub time1: f64,
     pub radius: f64,
     pub mat_ptr: Arc<dyn Material + Sync + Send>,
+    id: u32,
+    material_id: u32,
 }
 
 impl MovingSphere {
@@ -31,6 +34,8 @@ impl MovingSphere {
             time0,
             time1,
             radius,
+            id: new_object_id(),
+            material_id: material_id(&mat_ptr),
             mat_ptr,
         }
     }
@@ -72,7 +77,12 @@ impl Hittable for MovingSphere {
         rec.p = r.at(rec.t);
         let outward_normal = (rec.p - self.center(r.time())) / self.radius;
         rec.set_face_normal(r, &outward_normal);
+        (rec.u, rec.v) = get_sphere_uv(&outward_normal);
+        (rec.dpdu, rec.dpdv) = get_sphere_tangents(&outward_normal, self.radius);
         rec.mat_ptr = self.mat_ptr.clone();
+        rec.object_id = self.id;
+        rec.material_id = self.material_id;
+        rec.velocity = (self.center1 - self.center0) / (self.time1 - self.time0);
         true
     }
 
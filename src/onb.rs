@@ -0,0 +1,34 @@
+use crate::vec3::{cross, dot, Vec3};
+
+// Orthonormal basis with `w` along a surface normal, for working with
+// directions in a local shading frame where the normal is +z.
+#[derive(Debug, Clone, Copy)]
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+    pub fn build_from_w(n: &Vec3) -> Onb {
+        let w = n.unit_vector();
+        let a = if w.x.abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = cross(&w, &a).unit_vector();
+        let u = cross(&w, &v);
+        Onb { u, v, w }
+    }
+
+    // Local coordinates to world space.
+    pub fn local(&self, a: Vec3) -> Vec3 {
+        self.u * a.x + self.v * a.y + self.w * a.z
+    }
+
+    // World space to local coordinates.
+    pub fn to_local(&self, a: Vec3) -> Vec3 {
+        Vec3::new(dot(&a, &self.u), dot(&a, &self.v), dot(&a, &self.w))
+    }
+}
@@ -0,0 +1,381 @@
+use std::{
+    fs,
+    io::{self, Error, ErrorKind},
+    sync::Arc,
+};
+
+use crate::{
+    aabb::Aabb,
+    hittable::*,
+    material::Material,
+    ply::Ply,
+    ray::Ray,
+    sphere::{get_sphere_tangents, get_sphere_uv},
+    vec3::*,
+};
+
+// Particles per leaf of the hierarchy.
+const LEAF_SIZE: usize = 4;
+
+struct Node {
+    bbox: Aabb,
+    // Leaves hold `count` particles from `first`; inner nodes have their left
+    // child next in the array and their right child at `first`.
+    first: u32,
+    count: u32,
+    axis: u8,
+}
+
+// Large numbers of spheres, such as simulation dumps, kept in flat arrays of
+// single precision positions and radii with an index into a palette of
+// materials per particle, so a million of them take a few tens of
+// megabytes rather than a `Sphere` and a `BVHNode` each. The set has its own
+// hierarchy, built once and laid out flat, so it goes into a scene as a
+// single object. Hits fill in the record as `Sphere` does.
+pub struct ParticleSet {
+    positions: Vec<[f32; 3]>,
+    radii: Vec<f32>,
+    material_indices: Vec<u32>,
+    materials: Vec<Arc<dyn Material + Sync + Send>>,
+    material_ids: Vec<u32>,
+    nodes: Vec<Node>,
+    // Each particle is an object of its own, with ids from this one on.
+    first_id: u32,
+}
+
+impl ParticleSet {
+    pub fn new(
+        positions: Vec<[f32; 3]>,
+        radii: Vec<f32>,
+        material_indices: Vec<u32>,
+        materials: Vec<Arc<dyn Material + Sync + Send>>,
+    ) -> ParticleSet {
+        assert!(!positions.is_empty(), "a particle set needs particles");
+        assert_eq!(radii.len(), positions.len(), "wrong number of radii");
+        assert_eq!(
+            material_indices.len(),
+            positions.len(),
+            "wrong number of material indices"
+        );
+        assert!(
+            material_indices
+                .iter()
+                .all(|&m| (m as usize) < materials.len()),
+            "material index out of range"
+        );
+
+        let mut order: Vec<u32> = (0..positions.len() as u32).collect();
+        let mut nodes = Vec::with_capacity(2 * positions.len() / LEAF_SIZE + 1);
+        build(&mut nodes, &positions, &radii, &mut order, 0);
+
+        ParticleSet {
+            positions: order.iter().map(|&i| positions[i as usize]).collect(),
+            radii: order.iter().map(|&i| radii[i as usize]).collect(),
+            material_indices: order
+                .iter()
+                .map(|&i| material_indices[i as usize])
+                .collect(),
+            material_ids: materials.iter().map(material_id).collect(),
+            materials,
+            nodes,
+            first_id: new_object_ids(positions.len()),
+        }
+    }
+
+    // Particles from a CSV file of x, y, z and optionally radius and material
+    // index per line. A header line naming the columns (x, y, z, radius or r,
+    // material) allows them in any order and with other columns between.
+    // Particles without a radius get `default_radius`, and without a material
+    // the first of `materials`.
+    pub fn load_csv(
+        path: &str,
+        default_radius: f64,
+        materials: Vec<Arc<dyn Material + Sync + Send>>,
+    ) -> io::Result<ParticleSet> {
+        let text = fs::read_to_string(path)?;
+        let mut lines = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .peekable();
+
+        // Columns of x, y, z, radius and material.
+        let mut columns = [Some(0), Some(1), Some(2), Some(3), Some(4)];
+        if let Some(header) = lines.peek() {
+            let fields: Vec<String> = header
+                .split(',')
+                .map(|f| f.trim().to_ascii_lowercase())
+                .collect();
+            if fields.iter().any(|f| f.parse::<f64>().is_err()) {
+                let find = |names: &[&str]| fields.iter().position(|f| names.contains(&&f[..]));
+                columns = [
+                    find(&["x"]),
+                    find(&["y"]),
+                    find(&["z"]),
+                    find(&["radius", "r"]),
+                    find(&["material", "material_index"]),
+                ];
+                if columns[..3].iter().any(Option::is_none) {
+                    return Err(invalid("expected x, y and z columns"));
+                }
+                lines.next();
+            }
+        }
+
+        let mut particles = Particles::default();
+        for line in lines {
+            let fields = line
+                .split(',')
+                .map(|f| f.trim().parse::<f64>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| invalid("expected a number"))?;
+            let column = |c: Option<usize>| c.and_then(|c| fields.get(c).copied());
+            let (x, y, z) = match (column(columns[0]), column(columns[1]), column(columns[2])) {
+                (Some(x), Some(y), Some(z)) => (x, y, z),
+                _ => return Err(invalid("expected x, y and z")),
+            };
+            particles.push(
+                [x, y, z],
+                column(columns[3]).unwrap_or(default_radius),
+                column(columns[4]).unwrap_or(0.0),
+            );
+        }
+        particles.finish(materials)
+    }
+
+    // Particles from the vertices of a PLY file, with the optional vertex
+    // properties radius and material as in `load_csv`.
+    pub fn load_ply(
+        path: &str,
+        default_radius: f64,
+        materials: Vec<Arc<dyn Material + Sync + Send>>,
+    ) -> io::Result<ParticleSet> {
+        let ply = Ply::load(path)?;
+        let vertex = ply
+            .element("vertex")
+            .ok_or_else(|| invalid("no vertex element"))?;
+        let (x, y, z) = match (vertex.scalar("x"), vertex.scalar("y"), vertex.scalar("z")) {
+            (Some(x), Some(y), Some(z)) => (x, y, z),
+            _ => return Err(invalid("expected x, y and z vertex properties")),
+        };
+        let radius = vertex.scalar("radius");
+        let material = vertex
+            .scalar("material")
+            .or_else(|| vertex.scalar("material_index"));
+
+        let mut particles = Particles::default();
+        for i in 0..vertex.count {
+            particles.push(
+                [x[i], y[i], z[i]],
+                radius.map_or(default_radius, |r| r[i]),
+                material.map_or(0.0, |m| m[i]),
+            );
+        }
+        particles.finish(materials)
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    fn center(&self, i: usize) -> Point3 {
+        let [x, y, z] = self.positions[i];
+        Point3::new(x as f64, y as f64, z as f64)
+    }
+
+    // Nearer root of the particle's sphere within [t_min, t_max].
+    fn hit_particle(&self, r: &Ray, i: usize, t_min: f64, t_max: f64) -> Option<f64> {
+        let oc = *r.origin() - self.center(i);
+        let radius = self.radii[i] as f64;
+        let a = r.direction().length_squared();
+        let half_b = dot(&oc, r.direction());
+        let c = oc.length_squared() - radius * radius;
+        let discriminant = half_b * half_b - a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let sqrtd = discriminant.sqrt();
+        [(-half_b - sqrtd) / a, (-half_b + sqrtd) / a]
+            .into_iter()
+            .find(|&t| t_min <= t && t <= t_max)
+    }
+}
+
+impl Hittable for ParticleSet {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let mut closest: Option<(f64, usize)> = None;
+        let mut stack = [0usize; 64];
+        let mut top = 1;
+        while top > 0 {
+            top -= 1;
+            let node = &self.nodes[stack[top]];
+            let t_far = closest.map_or(t_max, |(t, _)| t);
+            if node.bbox.clip(r, t_min, t_far).is_none() {
+                continue;
+            }
+            if node.count > 0 {
+                let first = node.first as usize;
+                for i in first..first + node.count as usize {
+                    let t_far = closest.map_or(t_max, |(t, _)| t);
+                    if let Some(t) = self.hit_particle(r, i, t_min, t_far) {
+                        closest = Some((t, i));
+                    }
+                }
+                continue;
+            }
+            // Pushes the far child first so the near one is visited first.
+            let left = stack[top] + 1;
+            let right = node.first as usize;
+            let (near, far) = if r.direction()[node.axis as i32] < 0.0 {
+                (right, left)
+            } else {
+                (left, right)
+            };
+            stack[top] = far;
+            stack[top + 1] = near;
+            top += 2;
+        }
+        let (t, i) = match closest {
+            Some(hit) => hit,
+            None => return false,
+        };
+
+        let radius = self.radii[i] as f64;
+        rec.t = t;
+        rec.p = r.at(t);
+        let outward_normal = (rec.p - self.center(i)) / radius;
+        rec.set_face_normal(r, &outward_normal);
+        (rec.u, rec.v) = get_sphere_uv(&outward_normal);
+        (rec.dpdu, rec.dpdv) = get_sphere_tangents(&outward_normal, radius);
+        let material = self.material_indices[i] as usize;
+        rec.mat_ptr = self.materials[material].clone();
+        rec.object_id = self.first_id + i as u32;
+        rec.material_id = self.material_ids[material];
+        rec.velocity = Vec3::default();
+        true
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64, output_box: &mut Aabb) -> bool {
+        *output_box = self.nodes[0].bbox;
+        true
+    }
+}
+
+// Builds the node for the particles in `order`, which start at `offset` in
+// the final arrays, splitting them at the median along the longest axis of
+// their centres.
+fn build(
+    nodes: &mut Vec<Node>,
+    positions: &[[f32; 3]],
+    radii: &[f32],
+    order: &mut [u32],
+    offset: usize,
+) {
+    let mut lo = [f64::INFINITY; 3];
+    let mut hi = [f64::NEG_INFINITY; 3];
+    let mut centre_lo = lo;
+    let mut centre_hi = hi;
+    for &i in order.iter() {
+        let c = positions[i as usize];
+        let r = radii[i as usize] as f64;
+        for a in 0..3 {
+            let c = c[a] as f64;
+            lo[a] = lo[a].min(c - r);
+            hi[a] = hi[a].max(c + r);
+            centre_lo[a] = centre_lo[a].min(c);
+            centre_hi[a] = centre_hi[a].max(c);
+        }
+    }
+    let bbox = Aabb::new(
+        Point3::new(lo[0], lo[1], lo[2]),
+        Point3::new(hi[0], hi[1], hi[2]),
+    );
+
+    let index = nodes.len();
+    if order.len() <= LEAF_SIZE {
+        nodes.push(Node {
+            bbox,
+            first: offset as u32,
+            count: order.len() as u32,
+            axis: 0,
+        });
+        return;
+    }
+
+    let extent = [0, 1, 2].map(|a| centre_hi[a] - centre_lo[a]);
+    let axis = if extent[0] > extent[1] && extent[0] > extent[2] {
+        0
+    } else if extent[1] > extent[2] {
+        1
+    } else {
+        2
+    };
+    nodes.push(Node {
+        bbox,
+        first: 0,
+        count: 0,
+        axis: axis as u8,
+    });
+    let mid = order.len() / 2;
+    order.select_nth_unstable_by(mid, |&a, &b| {
+        positions[a as usize][axis].total_cmp(&positions[b as usize][axis])
+    });
+    let (left, right) = order.split_at_mut(mid);
+    build(nodes, positions, radii, left, offset);
+    nodes[index].first = nodes.len() as u32;
+    build(nodes, positions, radii, right, offset + mid);
+}
+
+// Particles read from a file, checked before they make a set.
+#[derive(Default)]
+struct Particles {
+    positions: Vec<[f32; 3]>,
+    radii: Vec<f32>,
+    material_indices: Vec<f64>,
+}
+
+impl Particles {
+    fn push(&mut self, [x, y, z]: [f64; 3], radius: f64, material: f64) {
+        self.positions.push([x as f32, y as f32, z as f32]);
+        self.radii.push(radius as f32);
+        self.material_indices.push(material);
+    }
+
+    fn finish(self, materials: Vec<Arc<dyn Material + Sync + Send>>) -> io::Result<ParticleSet> {
+        if self.positions.is_empty() {
+            return Err(invalid("no particles"));
+        }
+        // Checked as stored, so values too large for single precision fail too.
+        if !self.positions.iter().flatten().all(|c| c.is_finite()) {
+            return Err(invalid("position is not finite"));
+        }
+        if !self.radii.iter().all(|&r| r.is_finite() && r > 0.0) {
+            return Err(invalid("radius must be positive and finite"));
+        }
+        let material_indices = self
+            .material_indices
+            .iter()
+            .map(|&m| {
+                if m >= 0.0 && m.fract() == 0.0 && (m as usize) < materials.len() {
+                    Ok(m as u32)
+                } else {
+                    Err(invalid("material index out of range"))
+                }
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(ParticleSet::new(
+            self.positions,
+            self.radii,
+            material_indices,
+            materials,
+        ))
+    }
+}
+
+fn invalid(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg.to_string())
+}
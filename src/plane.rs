@@ -0,0 +1,55 @@
+use std::sync::Arc;
+
+use crate::{aabb::Aabb, hittable::*, material::Material, onb::Onb, ray::Ray, vec3::*};
+
+// Infinite plane through `point`. It has no bounding box, so it can't go in a
+// `BVHNode`; add it to a `Scene`, which keeps it outside the BVH. u and v
+// are distances from `point` along two directions in the plane.
+#[derive(Clone)]
+pub struct Plane {
+    point: Point3,
+    frame: Onb,
+    mat_ptr: Arc<dyn Material + Sync + Send>,
+    id: u32,
+    material_id: u32,
+}
+
+impl Plane {
+    pub fn new(point: Point3, normal: Vec3, mat_ptr: Arc<dyn Material + Sync + Send>) -> Plane {
+        Plane {
+            point,
+            frame: Onb::build_from_w(&normal),
+            id: new_object_id(),
+            material_id: material_id(&mat_ptr),
+            mat_ptr,
+        }
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let denom = dot(r.direction(), &self.frame.w);
+        if denom == 0.0 {
+            return false;
+        }
+        let t = dot(&(self.point - *r.origin()), &self.frame.w) / denom;
+        if t < t_min || t > t_max {
+            return false;
+        }
+        rec.t = t;
+        rec.p = r.at(t);
+        rec.set_face_normal(r, &self.frame.w);
+        let local = self.frame.to_local(rec.p - self.point);
+        (rec.u, rec.v) = (local.x, local.y);
+        (rec.dpdu, rec.dpdv) = (self.frame.u, self.frame.v);
+        rec.mat_ptr = self.mat_ptr.clone();
+        rec.object_id = self.id;
+        rec.material_id = self.material_id;
+        rec.velocity = Vec3::default();
+        true
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64, _output_box: &mut Aabb) -> bool {
+        false
+    }
+}
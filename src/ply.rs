@@ -0,0 +1,307 @@
+use std::{
+    fs,
+    io::{self, Error, ErrorKind},
+    str::SplitAsciiWhitespace,
+};
+
+// Values of one property across an element, converted to f64.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropertyValues {
+    Scalar(Vec<f64>),
+    List(Vec<Vec<f64>>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Property {
+    pub name: String,
+    pub values: PropertyValues,
+    // As stored, the type of the items for lists.
+    ty: ScalarType,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Element {
+    pub name: String,
+    pub count: usize,
+    pub properties: Vec<Property>,
+}
+
+impl Element {
+    pub fn scalar(&self, name: &str) -> Option<&[f64]> {
+        self.properties.iter().find_map(|p| match &p.values {
+            PropertyValues::Scalar(values) if p.name == name => Some(&values[..]),
+            _ => None,
+        })
+    }
+
+    // A scalar property scaled to [0, 1] if stored as an unsigned integer, as
+    // colours usually are, and as stored otherwise.
+    pub fn normalized(&self, name: &str) -> Option<Vec<f64>> {
+        let property = self.properties.iter().find(|p| p.name == name)?;
+        let scale = match property.ty {
+            ScalarType::U8 => 1.0 / u8::MAX as f64,
+            ScalarType::U16 => 1.0 / u16::MAX as f64,
+            ScalarType::U32 => 1.0 / u32::MAX as f64,
+            _ => 1.0,
+        };
+        Some(self.scalar(name)?.iter().map(|v| v * scale).collect())
+    }
+
+    pub fn list(&self, name: &str) -> Option<&[Vec<f64>]> {
+        self.properties.iter().find_map(|p| match &p.values {
+            PropertyValues::List(values) if p.name == name => Some(&values[..]),
+            _ => None,
+        })
+    }
+}
+
+// Contents of a PLY file (Turk's polygon file format), in ASCII or binary of
+// either byte order: a list of elements such as vertices and faces, each with
+// named scalar or list properties.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ply {
+    pub elements: Vec<Element>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScalarType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+// An element as declared in the header.
+struct ElementLayout {
+    name: String,
+    count: usize,
+    properties: Vec<(String, PropertyType)>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum PropertyType {
+    Scalar(ScalarType),
+    // Types of the length and of the items.
+    List(ScalarType, ScalarType),
+}
+
+impl Ply {
+    pub fn load(path: &str) -> io::Result<Ply> {
+        Ply::parse(&fs::read(path)?)
+    }
+
+    pub fn parse(bytes: &[u8]) -> io::Result<Ply> {
+        let end = b"end_header";
+        let header_end = bytes
+            .windows(end.len())
+            .position(|w| w == end)
+            .ok_or_else(|| invalid("missing end_header"))?;
+        // The body starts on the line after end_header.
+        let body_start = bytes[header_end..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(bytes.len(), |i| header_end + i + 1);
+        let header =
+            std::str::from_utf8(&bytes[..header_end]).map_err(|_| invalid("header is not text"))?;
+
+        let mut lines = header.lines().map(str::trim);
+        if lines.next() != Some("ply") {
+            return Err(invalid("not a PLY file"));
+        }
+        let mut format = None;
+        let mut layout: Vec<ElementLayout> = Vec::new();
+        for line in lines {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words[..] {
+                ["format", kind, _] => {
+                    format = Some(match kind {
+                        "ascii" => Format::Ascii,
+                        "binary_little_endian" => Format::BinaryLittleEndian,
+                        "binary_big_endian" => Format::BinaryBigEndian,
+                        _ => return Err(invalid("unknown format")),
+                    })
+                }
+                ["element", name, count] => {
+                    let count = count.parse().map_err(|_| invalid("bad element count"))?;
+                    layout.push(ElementLayout {
+                        name: name.to_string(),
+                        count,
+                        properties: Vec::new(),
+                    });
+                }
+                ["property", "list", length, item, name] => {
+                    let ty = PropertyType::List(scalar_type(length)?, scalar_type(item)?);
+                    push_property(&mut layout, name, ty)?;
+                }
+                ["property", ty, name] => {
+                    push_property(&mut layout, name, PropertyType::Scalar(scalar_type(ty)?))?;
+                }
+                ["comment", ..] | ["obj_info", ..] | [] => {}
+                _ => return Err(invalid("unrecognised header line")),
+            }
+        }
+        let format = format.ok_or_else(|| invalid("missing format"))?;
+
+        let body = &bytes[body_start..];
+        let mut source = match format {
+            Format::Ascii => Source::Ascii(
+                std::str::from_utf8(body)
+                    .map_err(|_| invalid("body is not text"))?
+                    .split_ascii_whitespace(),
+            ),
+            _ => Source::Binary {
+                bytes: body,
+                pos: 0,
+                big_endian: format == Format::BinaryBigEndian,
+            },
+        };
+
+        let mut elements = Vec::with_capacity(layout.len());
+        for ElementLayout {
+            name,
+            count,
+            properties: types,
+        } in layout
+        {
+            // Counts come from the file, so room is only made for as many
+            // values as the body could hold.
+            let capacity = count.min(body.len());
+            let mut properties: Vec<Property> = types
+                .iter()
+                .map(|(name, ty)| Property {
+                    name: name.clone(),
+                    ty: match ty {
+                        PropertyType::Scalar(t) | PropertyType::List(_, t) => *t,
+                    },
+                    values: match ty {
+                        PropertyType::Scalar(_) => {
+                            PropertyValues::Scalar(Vec::with_capacity(capacity))
+                        }
+                        PropertyType::List(..) => {
+                            PropertyValues::List(Vec::with_capacity(capacity))
+                        }
+                    },
+                })
+                .collect();
+            for _ in 0..count {
+                for (property, (_, ty)) in properties.iter_mut().zip(&types) {
+                    match (&mut property.values, ty) {
+                        (PropertyValues::Scalar(values), PropertyType::Scalar(t)) => {
+                            values.push(source.read(*t)?)
+                        }
+                        (PropertyValues::List(values), PropertyType::List(length, item)) => {
+                            let n = source.read(*length)?;
+                            if n < 0.0 || n.fract() != 0.0 {
+                                return Err(invalid("bad list length"));
+                            }
+                            let list = (0..n as usize)
+                                .map(|_| source.read(*item))
+                                .collect::<io::Result<_>>()?;
+                            values.push(list);
+                        }
+                        _ => unreachable!(),
+                    }
+                }
+            }
+            elements.push(Element {
+                name,
+                count,
+                properties,
+            });
+        }
+        Ok(Ply { elements })
+    }
+
+    pub fn element(&self, name: &str) -> Option<&Element> {
+        self.elements.iter().find(|e| e.name == name)
+    }
+}
+
+enum Source<'a> {
+    Ascii(SplitAsciiWhitespace<'a>),
+    Binary {
+        bytes: &'a [u8],
+        pos: usize,
+        big_endian: bool,
+    },
+}
+
+impl Source<'_> {
+    fn read(&mut self, ty: ScalarType) -> io::Result<f64> {
+        match self {
+            Source::Ascii(words) => words
+                .next()
+                .ok_or_else(|| invalid("unexpected end of file"))?
+                .parse()
+                .map_err(|_| invalid("expected a number")),
+            Source::Binary {
+                bytes,
+                pos,
+                big_endian,
+            } => {
+                let size = match ty {
+                    ScalarType::I8 | ScalarType::U8 => 1,
+                    ScalarType::I16 | ScalarType::U16 => 2,
+                    ScalarType::I32 | ScalarType::U32 | ScalarType::F32 => 4,
+                    ScalarType::F64 => 8,
+                };
+                let b = bytes
+                    .get(*pos..*pos + size)
+                    .ok_or_else(|| invalid("unexpected end of file"))?;
+                *pos += size;
+                let mut buf = [0u8; 8];
+                buf[..size].copy_from_slice(b);
+                if *big_endian {
+                    buf[..size].reverse();
+                }
+                Ok(match ty {
+                    ScalarType::I8 => buf[0] as i8 as f64,
+                    ScalarType::U8 => buf[0] as f64,
+                    ScalarType::I16 => i16::from_le_bytes([buf[0], buf[1]]) as f64,
+                    ScalarType::U16 => u16::from_le_bytes([buf[0], buf[1]]) as f64,
+                    ScalarType::I32 => i32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+                    ScalarType::U32 => u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+                    ScalarType::F32 => f32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+                    ScalarType::F64 => f64::from_le_bytes(buf),
+                })
+            }
+        }
+    }
+}
+
+fn scalar_type(name: &str) -> io::Result<ScalarType> {
+    Ok(match name {
+        "char" | "int8" => ScalarType::I8,
+        "uchar" | "uint8" => ScalarType::U8,
+        "short" | "int16" => ScalarType::I16,
+        "ushort" | "uint16" => ScalarType::U16,
+        "int" | "int32" => ScalarType::I32,
+        "uint" | "uint32" => ScalarType::U32,
+        "float" | "float32" => ScalarType::F32,
+        "double" | "float64" => ScalarType::F64,
+        _ => return Err(invalid("unknown property type")),
+    })
+}
+
+fn push_property(layout: &mut [ElementLayout], name: &str, ty: PropertyType) -> io::Result<()> {
+    let element = layout
+        .last_mut()
+        .ok_or_else(|| invalid("property before any element"))?;
+    element.properties.push((name.to_string(), ty));
+    Ok(())
+}
+
+fn invalid(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg.to_string())
+}
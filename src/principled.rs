@@ -0,0 +1,257 @@
+use std::sync::Arc;
+
+use crate::{
+    hittable::HitRecord,
+    material::{local_directions, reflectance, Material},
+    microfacet::{fresnel_dielectric, TrowbridgeReitz},
+    onb::Onb,
+    ray::Ray,
+    rtweekend::PI,
+    sampler::{sample_cosine_hemisphere, Sampler},
+    texture::{SolidColor, Texture},
+    vec3::{dot, reflect, refract, Color, Vec3},
+};
+
+// Disney-style principled BSDF: Burley diffuse with sheen, a GGX specular lobe
+// whose colour blends from dielectric to metal, a GGX clearcoat and smooth
+// transmission. Every parameter is a texture; scalar parameters read the first
+// channel. One lobe is picked per scatter and the result is weighted with the
+// pdf of all non-delta lobes combined, so lobes never double count.
+pub struct Principled {
+    pub base_color: Arc<dyn Texture + Send + Sync>,
+    pub metallic: Arc<dyn Texture + Send + Sync>,
+    pub roughness: Arc<dyn Texture + Send + Sync>,
+    pub specular: Arc<dyn Texture + Send + Sync>,
+    pub sheen: Arc<dyn Texture + Send + Sync>,
+    pub sheen_tint: Arc<dyn Texture + Send + Sync>,
+    pub clearcoat: Arc<dyn Texture + Send + Sync>,
+    pub clearcoat_roughness: Arc<dyn Texture + Send + Sync>,
+    pub transmission: Arc<dyn Texture + Send + Sync>,
+    pub ior: f64,
+}
+
+impl Principled {
+    // A rough dielectric with the given base colour; set the other fields with
+    // struct update syntax, e.g. `Principled { metallic, ..Principled::new(base) }`.
+    pub fn new(base_color: Arc<dyn Texture + Send + Sync>) -> Principled {
+        Principled {
+            base_color,
+            metallic: SolidColor::uniform(0.0),
+            roughness: SolidColor::uniform(0.5),
+            specular: SolidColor::uniform(0.5),
+            sheen: SolidColor::uniform(0.0),
+            sheen_tint: SolidColor::uniform(0.5),
+            clearcoat: SolidColor::uniform(0.0),
+            clearcoat_roughness: SolidColor::uniform(0.1),
+            transmission: SolidColor::uniform(0.0),
+            ior: 1.5,
+        }
+    }
+
+    pub(crate) fn lobes(&self, rec: &HitRecord) -> Lobes {
+        let (u, v, p) = (rec.u, rec.v, &rec.p);
+        let base = self.base_color.value(u, v, p);
+        let metallic = self.metallic.scalar(u, v, p).clamp(0.0, 1.0);
+        let roughness = self.roughness.scalar(u, v, p).clamp(0.0, 1.0);
+        let specular = self.specular.scalar(u, v, p).max(0.0);
+        let sheen_tint = self.sheen_tint.scalar(u, v, p).clamp(0.0, 1.0);
+        let transmission = self.transmission.scalar(u, v, p).clamp(0.0, 1.0);
+
+        let white = Color::new(1.0, 1.0, 1.0);
+        let tint = if luminance(base) > 0.0 {
+            base / luminance(base)
+        } else {
+            white
+        };
+        let alpha = TrowbridgeReitz::roughness_to_alpha(roughness).max(1e-3);
+        let cc_alpha = TrowbridgeReitz::roughness_to_alpha(
+            self.clearcoat_roughness.scalar(u, v, p).clamp(0.0, 1.0),
+        )
+        .max(1e-3);
+        Lobes {
+            base,
+            roughness,
+            diffuse_weight: (1.0 - metallic) * (1.0 - transmission),
+            specular_f0: lerp(white * (0.08 * specular), base, metallic),
+            specular: TrowbridgeReitz::new(alpha, alpha),
+            sheen: lerp(white, tint, sheen_tint) * self.sheen.scalar(u, v, p).max(0.0),
+            clearcoat_weight: 0.25 * self.clearcoat.scalar(u, v, p).max(0.0),
+            clearcoat: TrowbridgeReitz::new(cc_alpha, cc_alpha),
+            transmission_weight: (1.0 - metallic) * transmission,
+            ior: self.ior,
+        }
+    }
+}
+
+impl Material for Principled {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
+        let lobes = self.lobes(rec);
+        let frame = Onb::build_from_w(&rec.normal);
+        let wo = frame.to_local(-r_in.direction().unit_vector());
+        let u_lobe = sampler.get_1d();
+        let u = sampler.get_2d();
+        if wo.z <= 0.0 {
+            return false;
+        }
+
+        if !rec.front_face && lobes.transmission_weight > 0.0 {
+            // Leaving a transmissive object through a smooth interface.
+            let direction = lobes.sample_interface(wo, u_lobe);
+            *attenuation = Color::new(1.0, 1.0, 1.0);
+            *scattered = Ray::new(rec.p, frame.local(direction), r_in.time());
+            return true;
+        }
+
+        let p = lobes.probabilities(wo);
+        let wi = if u_lobe < p[3] {
+            // Delta transmission lobe: only it can produce this direction.
+            let wi = refract(-wo, Vec3::new(0.0, 0.0, 1.0), 1.0 / self.ior);
+            let f = lobes.base
+                * (lobes.transmission_weight * (1.0 - fresnel_dielectric(wo.z, self.ior)));
+            *attenuation = f / p[3];
+            *scattered = Ray::new(rec.p, frame.local(wi), r_in.time());
+            return true;
+        } else if u_lobe < p[3] + p[0] {
+            sample_cosine_hemisphere(u)
+        } else if u_lobe < p[3] + p[0] + p[1] {
+            reflect(-wo, lobes.specular.sample_wm(wo, u))
+        } else {
+            reflect(-wo, lobes.clearcoat.sample_wm(wo, u))
+        };
+        if wi.z <= 0.0 {
+            return false;
+        }
+
+        let pdf = lobes.pdf(wo, wi, &p);
+        if pdf <= 0.0 {
+            return false;
+        }
+        *attenuation = lobes.eval(wo, wi) / pdf;
+        *scattered = Ray::new(rec.p, frame.local(wi), r_in.time());
+        true
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Option<Color> {
+        let lobes = self.lobes(rec);
+        if !rec.front_face && lobes.transmission_weight > 0.0 {
+            return None;
+        }
+        let (wo, wi) = local_directions(r_in, rec, scattered);
+        Some(lobes.eval(wo, wi))
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let lobes = self.lobes(rec);
+        let (wo, wi) = local_directions(r_in, rec, scattered);
+        lobes.pdf(wo, wi, &lobes.probabilities(wo))
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.base_color.value(rec.u, rec.v, &rec.p)
+    }
+}
+
+// Principled parameters evaluated at one hit point. Directions are local, with
+// the shading normal along +z.
+pub(crate) struct Lobes {
+    base: Color,
+    roughness: f64,
+    diffuse_weight: f64,
+    specular_f0: Color,
+    specular: TrowbridgeReitz,
+    sheen: Color,
+    clearcoat_weight: f64,
+    clearcoat: TrowbridgeReitz,
+    transmission_weight: f64,
+    ior: f64,
+}
+
+impl Lobes {
+    // Selection probabilities of the diffuse, specular, clearcoat and
+    // transmission lobes, roughly proportional to what each reflects.
+    pub(crate) fn probabilities(&self, wo: Vec3) -> [f64; 4] {
+        let mut p = [
+            self.diffuse_weight * luminance(self.base),
+            luminance(schlick(self.specular_f0, wo.z)),
+            self.clearcoat_weight * reflectance(wo.z, 1.5),
+            self.transmission_weight * (1.0 - fresnel_dielectric(wo.z, self.ior)),
+        ];
+        let sum: f64 = p.iter().sum();
+        if sum <= 0.0 {
+            return [0.0, 1.0, 0.0, 0.0];
+        }
+        for x in p.iter_mut() {
+            *x /= sum;
+        }
+        p
+    }
+
+    // Sum of the non-delta lobes, times cos(theta_i).
+    pub(crate) fn eval(&self, wo: Vec3, wi: Vec3) -> Color {
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return Color::default();
+        }
+        let wh = (wo + wi).unit_vector();
+        let cos_d = dot(&wi, &wh);
+
+        // Burley diffuse with retro-reflection, plus sheen at grazing angles.
+        let fd90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+        let fl = 1.0 + (fd90 - 1.0) * (1.0 - wi.z).powi(5);
+        let fv = 1.0 + (fd90 - 1.0) * (1.0 - wo.z).powi(5);
+        let diffuse = (self.base * (fl * fv / PI) + self.sheen * (1.0 - cos_d).powi(5))
+            * self.diffuse_weight;
+
+        let specular = schlick(self.specular_f0, cos_d)
+            * (self.specular.d(wh) * self.specular.g(wo, wi) / (4.0 * wo.z * wi.z));
+
+        let clearcoat = self.clearcoat_weight
+            * reflectance(cos_d, 1.5)
+            * self.clearcoat.d(wh)
+            * self.clearcoat.g(wo, wi)
+            / (4.0 * wo.z * wi.z);
+
+        (diffuse + specular + Color::new(clearcoat, clearcoat, clearcoat)) * wi.z
+    }
+
+    // Combined density of the non-delta lobes for selection probabilities `p`.
+    pub(crate) fn pdf(&self, wo: Vec3, wi: Vec3, p: &[f64; 4]) -> f64 {
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return 0.0;
+        }
+        let wh = (wo + wi).unit_vector();
+        let reflect_jacobian = 1.0 / (4.0 * dot(&wo, &wh));
+        p[0] * wi.z / PI
+            + p[1] * self.specular.d_visible(wo, wh) * reflect_jacobian
+            + p[2] * self.clearcoat.d_visible(wo, wh) * reflect_jacobian
+    }
+
+    // Smooth dielectric interface seen from inside the object.
+    fn sample_interface(&self, wo: Vec3, u: f64) -> Vec3 {
+        let n = Vec3::new(0.0, 0.0, 1.0);
+        if u < fresnel_dielectric(wo.z, 1.0 / self.ior) {
+            reflect(-wo, n)
+        } else {
+            refract(-wo, n, self.ior)
+        }
+    }
+}
+
+fn schlick(f0: Color, cos_theta: f64) -> Color {
+    let m = (1.0 - cos_theta).clamp(0.0, 1.0).powi(5);
+    f0 + (Color::new(1.0, 1.0, 1.0) - f0) * m
+}
+
+fn lerp(a: Color, b: Color, t: f64) -> Color {
+    a * (1.0 - t) + b * t
+}
+
+pub fn luminance(c: Color) -> f64 {
+    0.2126 * c.x + 0.7152 * c.y + 0.0722 * c.z
+}
@@ -0,0 +1,88 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+// Thread-safe render progress: percentage, ETA and throughput, printed to stderr
+// on one line at most every `interval`.
+pub struct Progress {
+    total_samples: u64,
+    samples: AtomicU64,
+    rays: AtomicU64,
+    start: Instant,
+    interval: Duration,
+    last_report: Mutex<Instant>,
+}
+
+impl Progress {
+    pub fn new(total_samples: u64) -> Progress {
+        let start = Instant::now();
+        Progress {
+            total_samples,
+            samples: AtomicU64::new(0),
+            rays: AtomicU64::new(0),
+            start,
+            interval: Duration::from_millis(250),
+            last_report: Mutex::new(start),
+        }
+    }
+
+    pub fn add(&self, samples: u64, rays: u64) {
+        self.samples.fetch_add(samples, Ordering::Relaxed);
+        self.rays.fetch_add(rays, Ordering::Relaxed);
+        let mut last = self.last_report.lock().unwrap();
+        if last.elapsed() >= self.interval {
+            *last = Instant::now();
+            eprint!("\r{}", self.status());
+        }
+    }
+
+    pub fn finish(&self) {
+        eprintln!("\r{}", self.status());
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    pub fn status(&self) -> String {
+        let samples = self.samples.load(Ordering::Relaxed);
+        let rays = self.rays.load(Ordering::Relaxed);
+        let secs = self.elapsed().as_secs_f64();
+        let fraction = samples as f64 / self.total_samples.max(1) as f64;
+        let eta = if samples > 0 {
+            format_duration(secs / fraction - secs)
+        } else {
+            "--:--:--".to_string()
+        };
+        format!(
+            "{:5.1}% | elapsed {} | ETA {} | {} samples/s | {} rays/s   ",
+            100.0 * fraction,
+            format_duration(secs),
+            eta,
+            format_rate(samples as f64 / secs),
+            format_rate(rays as f64 / secs),
+        )
+    }
+}
+
+fn format_duration(secs: f64) -> String {
+    let s = secs.max(0.0).round() as u64;
+    format!("{:02}:{:02}:{:02}", s / 3600, s / 60 % 60, s % 60)
+}
+
+fn format_rate(rate: f64) -> String {
+    if !rate.is_finite() {
+        return "-".to_string();
+    }
+    if rate >= 1e6 {
+        format!("{:.2}M", rate / 1e6)
+    } else if rate >= 1e3 {
+        format!("{:.1}k", rate / 1e3)
+    } else {
+        format!("{:.0}", rate)
+    }
+}
@@ -0,0 +1,236 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb, disk::azimuth, hittable::*, material::Material, ray::Ray, rtweekend::PI, vec3::*,
+};
+
+// Quadric surface p^T A p + b . p + c = 0, with A symmetric, clipped to
+// `bbox` so unbounded ones such as paraboloids and hyperboloids can go in a
+// `BVHNode`. The positive side is outside and normals come from the gradient
+// 2 A p + b. u is the angle around the vertical axis through the centre of the
+// box and v the height within it; the tangents follow them within the tangent
+// plane.
+#[derive(Clone)]
+pub struct Quadric {
+    a: [[f64; 3]; 3],
+    b: Vec3,
+    c: f64,
+    bbox: Aabb,
+    mat_ptr: Arc<dyn Material + Sync + Send>,
+    id: u32,
+    material_id: u32,
+}
+
+impl Quadric {
+    pub fn new(
+        a: [[f64; 3]; 3],
+        b: Vec3,
+        c: f64,
+        bbox: Aabb,
+        mat_ptr: Arc<dyn Material + Sync + Send>,
+    ) -> Quadric {
+        Quadric {
+            a,
+            b,
+            c,
+            bbox,
+            id: new_object_id(),
+            material_id: material_id(&mat_ptr),
+            mat_ptr,
+        }
+    }
+
+    // (x/rx)^2 + (y/ry)^2 + (z/rz)^2 = 1 around `center`.
+    pub fn ellipsoid(
+        center: Point3,
+        radii: Vec3,
+        mat_ptr: Arc<dyn Material + Sync + Send>,
+    ) -> Quadric {
+        let a = diagonal(Vec3::new(
+            1.0 / (radii.x * radii.x),
+            1.0 / (radii.y * radii.y),
+            1.0 / (radii.z * radii.z),
+        ));
+        let bbox = Aabb::new(center - radii, center + radii);
+        Quadric::centered(a, Vec3::default(), -1.0, center, bbox, mat_ptr)
+    }
+
+    // Paraboloid of revolution with its vertex at `vertex`, opening upwards to
+    // `radius` at `height`.
+    pub fn paraboloid(
+        vertex: Point3,
+        radius: f64,
+        height: f64,
+        mat_ptr: Arc<dyn Material + Sync + Send>,
+    ) -> Quadric {
+        let a = diagonal(Vec3::new(1.0, 0.0, 1.0));
+        let b = Vec3::new(0.0, -radius * radius / height, 0.0);
+        let bbox = Aabb::new(
+            vertex - Vec3::new(radius, 0.0, radius),
+            vertex + Vec3::new(radius, height, radius),
+        );
+        Quadric::centered(a, b, 0.0, vertex, bbox, mat_ptr)
+    }
+
+    // Hyperboloid of one sheet around a vertical axis: a waist of
+    // `waist_radius` at `center`, widening to `end_radius` at `height` / 2
+    // above and below.
+    pub fn hyperboloid(
+        center: Point3,
+        waist_radius: f64,
+        end_radius: f64,
+        height: f64,
+        mat_ptr: Arc<dyn Material + Sync + Send>,
+    ) -> Quadric {
+        let half = height / 2.0;
+        let k = (end_radius * end_radius - waist_radius * waist_radius) / (half * half);
+        let a = diagonal(Vec3::new(1.0, -k, 1.0));
+        let extent = Vec3::new(end_radius, half, end_radius);
+        let bbox = Aabb::new(center - extent, center + extent);
+        Quadric::centered(
+            a,
+            Vec3::default(),
+            -waist_radius * waist_radius,
+            center,
+            bbox,
+            mat_ptr,
+        )
+    }
+
+    // Hyperboloid of two sheets around a vertical axis, with vertices
+    // `vertex_distance` above and below `center`, widening to `end_radius` at
+    // `height` / 2 above and below.
+    pub fn two_sheet_hyperboloid(
+        center: Point3,
+        vertex_distance: f64,
+        end_radius: f64,
+        height: f64,
+        mat_ptr: Arc<dyn Material + Sync + Send>,
+    ) -> Quadric {
+        let half = height / 2.0;
+        let v2 = vertex_distance * vertex_distance;
+        let b2 = end_radius * end_radius / (half * half / v2 - 1.0);
+        let a = diagonal(Vec3::new(-1.0 / b2, 1.0 / v2, -1.0 / b2));
+        let extent = Vec3::new(end_radius, half, end_radius);
+        let bbox = Aabb::new(center - extent, center + extent);
+        // Negated so the inside of each sheet is negative.
+        Quadric::centered(negate(a), Vec3::default(), 1.0, center, bbox, mat_ptr)
+    }
+
+    pub fn with_bbox(mut self, bbox: Aabb) -> Quadric {
+        self.bbox = bbox;
+        self
+    }
+
+    // The quadric given around the origin, moved to `center`.
+    fn centered(
+        a: [[f64; 3]; 3],
+        b: Vec3,
+        c: f64,
+        center: Point3,
+        bbox: Aabb,
+        mat_ptr: Arc<dyn Material + Sync + Send>,
+    ) -> Quadric {
+        // f(p - center) expanded in p.
+        let ac = mul(&a, center);
+        Quadric {
+            a,
+            b: b - ac * 2.0,
+            c: dot(&center, &ac) - dot(&b, &center) + c,
+            bbox,
+            id: new_object_id(),
+            material_id: material_id(&mat_ptr),
+            mat_ptr,
+        }
+    }
+
+    pub fn value(&self, p: Point3) -> f64 {
+        dot(&p, &mul(&self.a, p)) + dot(&self.b, &p) + self.c
+    }
+
+    pub fn gradient(&self, p: Point3) -> Vec3 {
+        mul(&self.a, p) * 2.0 + self.b
+    }
+
+    fn contains(&self, p: Point3) -> bool {
+        let eps = 1e-9 * (1.0 + p.length());
+        (0..3).all(|i| self.bbox.minimum[i] - eps <= p[i] && p[i] <= self.bbox.maximum[i] + eps)
+    }
+}
+
+impl Hittable for Quadric {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let (o, d) = (*r.origin(), *r.direction());
+        let ad = mul(&self.a, d);
+        let qa = dot(&d, &ad);
+        let qb = 2.0 * dot(&o, &ad) + dot(&self.b, &d);
+        let qc = self.value(o);
+
+        let mut roots = Vec::with_capacity(2);
+        if qa.abs() < 1e-12 * d.length_squared() {
+            if qb != 0.0 {
+                roots.push(-qc / qb);
+            }
+        } else {
+            let discriminant = qb * qb - 4.0 * qa * qc;
+            if discriminant < 0.0 {
+                return false;
+            }
+            // Avoids the cancellation of -b + sqrt(b^2 - 4ac) when 4ac is small.
+            let q = -0.5 * (qb + qb.signum() * discriminant.sqrt());
+            let (t0, t1) = (q / qa, if q != 0.0 { qc / q } else { q / qa });
+            roots.extend([t0.min(t1), t0.max(t1)]);
+        }
+        let t = match roots
+            .into_iter()
+            .find(|&t| t_min <= t && t <= t_max && self.contains(r.at(t)))
+        {
+            Some(t) => t,
+            None => return false,
+        };
+
+        rec.t = t;
+        rec.p = r.at(t);
+        let outward_normal = self.gradient(rec.p);
+        if outward_normal.near_zero() {
+            return false;
+        }
+        let outward_normal = outward_normal.unit_vector();
+        rec.set_face_normal(r, &outward_normal);
+
+        let center = (self.bbox.minimum + self.bbox.maximum) * 0.5;
+        let height = self.bbox.maximum.y - self.bbox.minimum.y;
+        let local = rec.p - center;
+        let in_plane = |v: Vec3| v - outward_normal * dot(&v, &outward_normal);
+        rec.u = azimuth(Vec3::new(local.x, -local.z, 0.0)) / (2.0 * PI);
+        rec.v = (rec.p.y - self.bbox.minimum.y) / height;
+        rec.dpdu = in_plane(Vec3::new(local.z, 0.0, -local.x) * (2.0 * PI));
+        rec.dpdv = in_plane(Vec3::new(0.0, height, 0.0));
+        rec.mat_ptr = self.mat_ptr.clone();
+        rec.object_id = self.id;
+        rec.material_id = self.material_id;
+        rec.velocity = Vec3::default();
+        true
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64, output_box: &mut Aabb) -> bool {
+        *output_box = self.bbox;
+        true
+    }
+}
+
+fn diagonal(v: Vec3) -> [[f64; 3]; 3] {
+    [[v.x, 0.0, 0.0], [0.0, v.y, 0.0], [0.0, 0.0, v.z]]
+}
+
+fn negate(a: [[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    a.map(|row| row.map(|x| -x))
+}
+
+fn mul(a: &[[f64; 3]; 3], v: Vec3) -> Vec3 {
+    Vec3::new(
+        a[0][0] * v.x + a[0][1] * v.y + a[0][2] * v.z,
+        a[1][0] * v.x + a[1][1] * v.y + a[1][2] * v.z,
+        a[2][0] * v.x + a[2][1] * v.y + a[2][2] * v.z,
+    )
+}
@@ -4,6 +4,8 @@ pub struct Ray {
     origin: Point3,
     direction: Vec3,
     time: f64,
+    // Hero wavelength in nanometres when rendering spectrally.
+    wavelength: Option<f64>,
 }
 
 impl Ray {
@@ -12,9 +14,15 @@ impl Ray {
             origin,
             direction,
             time,
+            wavelength: None,
         }
     }
 
+    pub fn with_wavelength(mut self, wavelength: Option<f64>) -> Self {
+        self.wavelength = wavelength;
+        self
+    }
+
     pub fn origin(&self) -> &Point3 {
         &self.origin
     }
@@ -30,4 +38,8 @@ impl Ray {
     pub fn time(&self) -> f64 {
         self.time
     }
+
+    pub fn wavelength(&self) -> Option<f64> {
+        self.wavelength
+    }
 }
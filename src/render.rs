@@ -0,0 +1,302 @@
+use std::{
+    cell::Cell,
+    ops::Range,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
+
+use crate::{
+    camera::Camera,
+    film::{Film, FilmTile, SampleFeatures},
+    hittable::{HitRecord, Hittable},
+    medium::{Interaction, MediumStack},
+    progress::Progress,
+    ray::Ray,
+    rtweekend::INFINITY,
+    sampler::Sampler,
+    spectrum::SampledWavelengths,
+    vec3::{dot, Color, Vec3},
+};
+
+pub struct RenderSettings {
+    pub samples_per_pixel: usize,
+    pub max_depth: i32,
+    pub tile_size: u32,
+    // The image is rendered in passes of this many samples per pixel, so a
+    // snapshot taken mid-render shows the whole image at lower quality.
+    pub samples_per_pass: usize,
+    pub snapshot: Option<Snapshot>,
+    // Trace hero wavelengths instead of RGB, so dispersive glass splits light.
+    pub spectral: bool,
+}
+
+// Writes the in-progress image to `path` every `interval` while rendering.
+pub struct Snapshot {
+    pub path: String,
+    pub interval: Duration,
+}
+
+thread_local! {
+    // Rays traced by this thread since the counter was last drained.
+    static RAYS_TRACED: Cell<u64> = const { Cell::new(0) };
+}
+
+// State carried along one camera path.
+#[derive(Debug, Clone, Default)]
+pub struct PathState {
+    // Dielectric volumes the path is inside, for absorption and nested
+    // interfaces.
+    pub media: MediumStack,
+    // Wavelengths of the path in spectral mode; colours returned by
+    // `ray_color` then hold one spectral sample per wavelength.
+    pub wavelengths: Option<SampledWavelengths>,
+    // Scattering events inside media so far.
+    pub scatter_events: u32,
+}
+
+// Longest random walk followed inside a scattering medium.
+const MAX_SCATTER_EVENTS: u32 = 256;
+
+impl PathState {
+    pub fn new(wavelengths: Option<SampledWavelengths>) -> PathState {
+        PathState {
+            media: MediumStack::default(),
+            wavelengths,
+            scatter_events: 0,
+        }
+    }
+
+    // An RGB colour as seen by this path.
+    fn sample(&self, c: Color) -> Color {
+        match &self.wavelengths {
+            Some(wavelengths) => wavelengths.reflectance(c),
+            None => c,
+        }
+    }
+}
+
+// `primary` receives the first intersection of the camera ray (None on a miss)
+// for the feature buffers; deeper bounces pass None.
+pub fn ray_color(
+    r: &Ray,
+    world: &Arc<dyn Hittable + Send + Sync>,
+    depth: i32,
+    sampler: &mut dyn Sampler,
+    primary: Option<&mut Option<HitRecord>>,
+    path: &mut PathState,
+) -> Color {
+    let mut rec = HitRecord::default();
+
+    if depth <= 0 {
+        return Color::default();
+    }
+
+    RAYS_TRACED.with(|rays| rays.set(rays.get() + 1));
+    let hit = world.hit(r, 0.001, INFINITY, &mut rec);
+    if hit {
+        if let Some(primary) = primary {
+            *primary = Some(rec.clone());
+        }
+    }
+    // Light coming back along this segment is absorbed, or scattered, by the
+    // medium it travelled through.
+    let length = r.direction().length();
+    let distance = if hit { rec.t * length } else { INFINITY };
+    let unit_direction = *r.direction() / length;
+    let transmittance = match path
+        .media
+        .sample_interaction(unit_direction, distance, sampler)
+    {
+        Interaction::Pass { weight } => path.sample(weight),
+        Interaction::Scatter {
+            distance,
+            direction,
+            weight,
+        } => {
+            // Steps of a random walk don't count as bounces, but the walk
+            // is cut off eventually.
+            path.scatter_events += 1;
+            if path.scatter_events > MAX_SCATTER_EVENTS {
+                return Color::default();
+            }
+            let scattered = Ray::new(r.at(distance / length), direction, r.time())
+                .with_wavelength(r.wavelength());
+            return path.sample(weight) * ray_color(&scattered, world, depth, sampler, None, path);
+        }
+    };
+    if hit {
+        let medium = rec
+            .mat_ptr
+            .medium(r.wavelength())
+            .map(|m| (rec.material_id, m));
+        if let Some((id, m)) = medium {
+            match path.media.interface(id, &m, rec.front_face) {
+                Some(outside_ior) => rec.outside_ior = outside_ior,
+                None => {
+                    // Boundary hidden inside a higher priority medium.
+                    path.media.cross(id, m, rec.front_face);
+                    let continued =
+                        Ray::new(rec.p, *r.direction(), r.time()).with_wavelength(r.wavelength());
+                    return transmittance
+                        * ray_color(&continued, world, depth, sampler, None, path);
+                }
+            }
+        }
+        let mut scattered = Ray::new(Vec3::default(), Vec3::default(), 0.0);
+        let mut attenuation = Color::default();
+        if rec
+            .mat_ptr
+            .scatter(r, &rec, &mut attenuation, &mut scattered, sampler)
+        {
+            if let Some((id, m)) = medium {
+                if dot(scattered.direction(), &rec.geometric_normal) < 0.0 {
+                    path.media.cross(id, m, rec.front_face);
+                }
+            }
+            if rec.mat_ptr.dispersive() {
+                if let Some(wavelengths) = &mut path.wavelengths {
+                    wavelengths.terminate_secondary();
+                }
+            }
+            let attenuation = path.sample(attenuation);
+            let scattered = scattered.with_wavelength(r.wavelength());
+            return transmittance
+                * attenuation
+                * ray_color(&scattered, world, depth - 1, sampler, None, path);
+        }
+        return Color::default();
+    }
+    transmittance * path.sample(background(r))
+}
+
+pub fn background(r: &Ray) -> Color {
+    let unit_direction = r.direction().unit_vector();
+    let t = 0.5 * (unit_direction.y + 1.0);
+    Color::new(1.0, 1.0, 1.0) * (1.0 - t) + Color::new(0.5, 0.7, 1.0) * t
+}
+
+// Renders the film in passes; within a pass tiles are rendered in parallel and
+// splatted into the film as they finish.
+pub fn render<S: Sampler + Clone + Send + Sync>(
+    world: &Arc<dyn Hittable + Send + Sync>,
+    cam: &Camera,
+    settings: &RenderSettings,
+    sampler: &S,
+    film: &mut Film,
+) {
+    let tiles = film.tiles(settings.tile_size);
+    let pixels = film.width as u64 * film.height as u64;
+    let progress = Progress::new(pixels * settings.samples_per_pixel as u64);
+    let samples_per_pass = settings.samples_per_pass.max(1);
+    let last_snapshot = Mutex::new(Instant::now());
+    let film = Mutex::new(film);
+
+    for first in (0..settings.samples_per_pixel).step_by(samples_per_pass) {
+        let samples = first..usize::min(first + samples_per_pass, settings.samples_per_pixel);
+        tiles.par_iter().for_each(|bounds| {
+            let tile = {
+                let film = film.lock().unwrap();
+                film.tile(*bounds)
+            };
+            let tile = render_tile(world, cam, settings, sampler, tile, samples.clone(), &progress);
+            let mut film = film.lock().unwrap();
+            film.merge_tile(tile);
+            if let Some(snapshot) = &settings.snapshot {
+                let mut last = last_snapshot.lock().unwrap();
+                if last.elapsed() >= snapshot.interval {
+                    *last = Instant::now();
+                    if let Err(e) = film.to_image().save(&snapshot.path) {
+                        eprintln!("\nfailed to write snapshot {}: {}", snapshot.path, e);
+                    }
+                }
+            }
+        });
+    }
+    progress.finish();
+}
+
+fn render_tile<S: Sampler + Clone>(
+    world: &Arc<dyn Hittable + Send + Sync>,
+    cam: &Camera,
+    settings: &RenderSettings,
+    sampler: &S,
+    mut tile: FilmTile,
+    samples: Range<usize>,
+    progress: &Progress,
+) -> FilmTile {
+    let bounds = tile.sample_bounds;
+    let (width, height) = tile.film_size;
+    let mut sampler = sampler.clone();
+    for y in bounds.y0..bounds.y1 {
+        for x in bounds.x0..bounds.x1 {
+            for s in samples.clone() {
+                sampler.start_pixel_sample(x, y, s);
+                let (dx, dy) = sampler.get_2d();
+                let (film_x, film_y) = (x as f64 + dx, y as f64 + dy);
+                let u = film_x / width as f64;
+                let v = 1.0 - film_y / height as f64;
+                let wavelengths = settings
+                    .spectral
+                    .then(|| SampledWavelengths::sample_uniform(sampler.get_1d()));
+                let r = cam
+                    .get_ray(u, v, &mut sampler)
+                    .with_wavelength(wavelengths.map(|w| w.hero()));
+                let mut primary = None;
+                let mut path = PathState::new(wavelengths);
+                let mut color = ray_color(
+                    &r,
+                    world,
+                    settings.max_depth,
+                    &mut sampler,
+                    Some(&mut primary),
+                    &mut path,
+                );
+                if let Some(wavelengths) = path.wavelengths {
+                    color = wavelengths.to_rgb(color);
+                }
+                tile.add_sample(film_x, film_y, color);
+                let features = match primary {
+                    Some(rec) => primary_features(&r, &rec, cam, (width, height)),
+                    None => SampleFeatures {
+                        albedo: background(&r),
+                        depth: INFINITY,
+                        ..Default::default()
+                    },
+                };
+                tile.add_features(x, y, &features);
+            }
+            let rays = RAYS_TRACED.with(|rays| rays.replace(0));
+            progress.add(samples.len() as u64, rays);
+        }
+    }
+    tile
+}
+
+fn primary_features(
+    r: &Ray,
+    rec: &HitRecord,
+    cam: &Camera,
+    (width, height): (u32, u32),
+) -> SampleFeatures {
+    // Where the surface point was at the start and end of the shutter.
+    let p0 = rec.p + rec.velocity * (cam.time0 - r.time());
+    let p1 = rec.p + rec.velocity * (cam.time1 - r.time());
+    let motion = match (cam.project(p0), cam.project(p1)) {
+        (Some((s0, t0)), Some((s1, t1))) => Vec3::new(
+            (s1 - s0) * width as f64,
+            -(t1 - t0) * height as f64,
+            0.0,
+        ),
+        _ => Vec3::default(),
+    };
+    SampleFeatures {
+        albedo: rec.mat_ptr.albedo(rec),
+        normal: rec.normal,
+        depth: rec.t * r.direction().length(),
+        motion,
+        object_id: rec.object_id,
+        material_id: rec.material_id,
+    }
+}
@@ -0,0 +1,82 @@
+// Real roots in [t0, t1] of the polynomial with coefficients `coeffs`,
+// constant term first, in increasing order. The roots of the derivative split
+// the interval into pieces where the polynomial is monotonic, and each piece
+// with a sign change holds exactly one root, found by safeguarded Newton
+// iteration. Unlike closed form solutions, which lose most of their precision
+// on the nearly double roots of grazing rays, this stays accurate for any
+// degree. Roots of even multiplicity, where the sign doesn't change, are
+// missed.
+pub fn polynomial_roots(coeffs: &[f64], t0: f64, t1: f64) -> Vec<f64> {
+    let mut roots = Vec::new();
+    if coeffs.len() < 2 || t0 > t1 {
+        return roots;
+    }
+    if coeffs.len() == 2 {
+        if coeffs[1] != 0.0 {
+            let t = -coeffs[0] / coeffs[1];
+            if t0 <= t && t <= t1 {
+                roots.push(t);
+            }
+        }
+        return roots;
+    }
+
+    let derivative: Vec<f64> = coeffs
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(i, c)| c * i as f64)
+        .collect();
+    let mut bounds = vec![t0];
+    bounds.extend(polynomial_roots(&derivative, t0, t1));
+    bounds.push(t1);
+
+    for w in bounds.windows(2) {
+        let (a, b) = (w[0], w[1]);
+        let (fa, fb) = (evaluate(coeffs, a), evaluate(coeffs, b));
+        if fa == 0.0 {
+            if roots.last() != Some(&a) {
+                roots.push(a);
+            }
+        } else if fa * fb < 0.0 {
+            roots.push(bracketed_root(coeffs, &derivative, a, b, fa));
+        }
+    }
+    if evaluate(coeffs, t1) == 0.0 && roots.last() != Some(&t1) {
+        roots.push(t1);
+    }
+    roots
+}
+
+pub fn evaluate(coeffs: &[f64], t: f64) -> f64 {
+    coeffs.iter().rev().fold(0.0, |acc, c| acc * t + c)
+}
+
+// Root of a polynomial with a single sign change over [a, b], where it is
+// fa at a. Newton steps that leave the bracket are replaced by bisection.
+fn bracketed_root(coeffs: &[f64], derivative: &[f64], mut a: f64, mut b: f64, fa: f64) -> f64 {
+    let mut t = 0.5 * (a + b);
+    for _ in 0..100 {
+        let f = evaluate(coeffs, t);
+        if f == 0.0 {
+            return t;
+        }
+        if (f < 0.0) == (fa < 0.0) {
+            a = t;
+        } else {
+            b = t;
+        }
+        let df = evaluate(derivative, t);
+        let newton = t - f / df;
+        let next = if df != 0.0 && a < newton && newton < b {
+            newton
+        } else {
+            0.5 * (a + b)
+        };
+        if (next - t).abs() <= 1e-12 * (1.0 + t.abs()) {
+            return next;
+        }
+        t = next;
+    }
+    t
+}
@@ -1,6 +1,6 @@
 use rand::Rng;
 
-pub const INFINITY: f64 = std::f64::INFINITY;
+pub const INFINITY: f64 = f64::INFINITY;
 pub const PI: f64 = std::f64::consts::PI;
 
 pub fn degrees_to_radians(degrees: f64) -> f64 {
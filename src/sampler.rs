@@ -0,0 +1,434 @@
+use crate::{
+    rtweekend::PI,
+    vec3::{Point3, Vec3},
+};
+
+const ONE_MINUS_EPSILON: f64 = 1.0 - f64::EPSILON / 2.0;
+
+// Hands out sample dimensions for one pixel sample at a time. Callers start a
+// pixel sample, then pull 1D/2D values in the same order for every sample (camera
+// first, then the materials along the path) so dimensions line up across a pixel.
+pub trait Sampler {
+    fn samples_per_pixel(&self) -> usize;
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: usize);
+    fn get_1d(&mut self) -> f64;
+    fn get_2d(&mut self) -> (f64, f64);
+}
+
+// Per-pixel sample state shared by every sampler: which pixel sample we are on
+// and how many dimensions have been consumed so far.
+#[derive(Debug, Clone, Copy, Default)]
+struct PixelSample {
+    x: u32,
+    y: u32,
+    index: usize,
+    dimension: u32,
+}
+
+impl PixelSample {
+    fn start(&mut self, x: u32, y: u32, index: usize) {
+        self.x = x;
+        self.y = y;
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    // Hash of the pixel and the current dimension, used to seed scrambles.
+    fn hash(&self, seed: u64) -> u64 {
+        hash(&[self.x as u64, self.y as u64, self.dimension as u64, seed])
+    }
+
+    fn next_dimension(&mut self, n: u32) -> u32 {
+        let d = self.dimension;
+        self.dimension += n;
+        d
+    }
+}
+
+// White noise: every dimension is an independent uniform value.
+#[derive(Debug, Clone)]
+pub struct IndependentSampler {
+    samples_per_pixel: usize,
+    seed: u64,
+    state: PixelSample,
+}
+
+impl IndependentSampler {
+    pub fn new(samples_per_pixel: usize, seed: u64) -> IndependentSampler {
+        IndependentSampler {
+            samples_per_pixel,
+            seed,
+            state: PixelSample::default(),
+        }
+    }
+
+    fn uniform(&mut self) -> f64 {
+        let h = hash(&[
+            self.state.x as u64,
+            self.state.y as u64,
+            self.state.index as u64,
+            self.state.next_dimension(1) as u64,
+            self.seed,
+        ]);
+        to_unit_float(h)
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn samples_per_pixel(&self) -> usize {
+        self.samples_per_pixel
+    }
+
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: usize) {
+        self.state.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.uniform()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.uniform(), self.uniform())
+    }
+}
+
+// Jittered stratification: each dimension is split into `samples_per_pixel`
+// strata (an x_strata * y_strata grid for 2D) and every pixel sample lands in a
+// different stratum. Strata are visited in a per-pixel, per-dimension random
+// order so that dimensions stay decorrelated.
+#[derive(Debug, Clone)]
+pub struct StratifiedSampler {
+    samples_per_pixel: usize,
+    x_strata: usize,
+    y_strata: usize,
+    seed: u64,
+    state: PixelSample,
+}
+
+impl StratifiedSampler {
+    pub fn new(samples_per_pixel: usize, seed: u64) -> StratifiedSampler {
+        let samples_per_pixel = samples_per_pixel.max(1);
+        // Use the most square factorisation of the sample count for 2D strata.
+        let mut x_strata = (samples_per_pixel as f64).sqrt() as usize;
+        while !samples_per_pixel.is_multiple_of(x_strata) {
+            x_strata -= 1;
+        }
+        StratifiedSampler {
+            samples_per_pixel,
+            x_strata,
+            y_strata: samples_per_pixel / x_strata,
+            seed,
+            state: PixelSample::default(),
+        }
+    }
+
+    fn jitter(&self, h: u64) -> f64 {
+        to_unit_float(mix_bits(h ^ self.state.index as u64))
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn samples_per_pixel(&self) -> usize {
+        self.samples_per_pixel
+    }
+
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: usize) {
+        self.state.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let h = self.state.hash(self.seed);
+        self.state.next_dimension(1);
+        let stratum = permutation_element(
+            self.state.index % self.samples_per_pixel,
+            self.samples_per_pixel,
+            h,
+        );
+        f64::min(
+            (stratum as f64 + self.jitter(h)) / self.samples_per_pixel as f64,
+            ONE_MINUS_EPSILON,
+        )
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let h = self.state.hash(self.seed);
+        self.state.next_dimension(2);
+        let stratum = permutation_element(
+            self.state.index % self.samples_per_pixel,
+            self.samples_per_pixel,
+            h,
+        );
+        let (sx, sy) = (stratum % self.x_strata, stratum / self.x_strata);
+        let dx = self.jitter(mix_bits(h));
+        let dy = self.jitter(mix_bits(h ^ 0x9e37_79b9_7f4a_7c15));
+        (
+            f64::min((sx as f64 + dx) / self.x_strata as f64, ONE_MINUS_EPSILON),
+            f64::min((sy as f64 + dy) / self.y_strata as f64, ONE_MINUS_EPSILON),
+        )
+    }
+}
+
+const PRIMES: [u64; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89,
+    97, 101, 103, 107, 109, 113, 127, 131,
+];
+
+// Halton sequence with Owen-scrambled digits, one prime base per dimension and a
+// fresh scramble per pixel. Dimensions past the prime table fall back to
+// independent values.
+#[derive(Debug, Clone)]
+pub struct HaltonSampler {
+    samples_per_pixel: usize,
+    seed: u64,
+    state: PixelSample,
+}
+
+impl HaltonSampler {
+    pub fn new(samples_per_pixel: usize, seed: u64) -> HaltonSampler {
+        HaltonSampler {
+            samples_per_pixel,
+            seed,
+            state: PixelSample::default(),
+        }
+    }
+
+    fn sample_dimension(&mut self) -> f64 {
+        let h = self.state.hash(self.seed);
+        let dimension = self.state.next_dimension(1) as usize;
+        if dimension >= PRIMES.len() {
+            return to_unit_float(mix_bits(h ^ self.state.index as u64));
+        }
+        owen_scrambled_radical_inverse(PRIMES[dimension], self.state.index as u64, h)
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn samples_per_pixel(&self) -> usize {
+        self.samples_per_pixel
+    }
+
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: usize) {
+        self.state.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.sample_dimension()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.sample_dimension(), self.sample_dimension())
+    }
+}
+
+// Owen-scrambled Sobol points (Burley 2020, "Practical Hash-based Owen
+// Scrambling"). Every 1D/2D request uses the first one or two Sobol dimensions
+// with its own shuffle and scramble seed, which keeps the 2D stratification of
+// the (0, 2)-sequence for every pair while padding to any number of dimensions.
+#[derive(Debug, Clone)]
+pub struct SobolSampler {
+    samples_per_pixel: usize,
+    seed: u64,
+    state: PixelSample,
+}
+
+impl SobolSampler {
+    pub fn new(samples_per_pixel: usize, seed: u64) -> SobolSampler {
+        SobolSampler {
+            samples_per_pixel,
+            seed,
+            state: PixelSample::default(),
+        }
+    }
+
+    fn shuffled_index(&self, h: u64) -> u32 {
+        nested_uniform_scramble(self.state.index as u32, h as u32)
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn samples_per_pixel(&self) -> usize {
+        self.samples_per_pixel
+    }
+
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: usize) {
+        self.state.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let h = self.state.hash(self.seed);
+        self.state.next_dimension(1);
+        let index = self.shuffled_index(h);
+        sobol_scrambled(index, 0, mix_bits(h ^ 1) as u32)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let h = self.state.hash(self.seed);
+        self.state.next_dimension(2);
+        let index = self.shuffled_index(h);
+        (
+            sobol_scrambled(index, 0, mix_bits(h ^ 1) as u32),
+            sobol_scrambled(index, 1, mix_bits(h ^ 2) as u32),
+        )
+    }
+}
+
+const SOBOL_MATRICES: [[u32; 32]; 2] = sobol_matrices();
+
+// Direction numbers of the first two Sobol dimensions: van der Corput and the
+// one generated by the primitive polynomial x + 1.
+const fn sobol_matrices() -> [[u32; 32]; 2] {
+    let mut m = [[0u32; 32]; 2];
+    let mut i = 0;
+    while i < 32 {
+        m[0][i] = 1 << (31 - i);
+        m[1][i] = if i == 0 {
+            1 << 31
+        } else {
+            m[1][i - 1] ^ (m[1][i - 1] >> 1)
+        };
+        i += 1;
+    }
+    m
+}
+
+fn sobol(mut index: u32, dimension: usize) -> u32 {
+    let mut v = 0;
+    let mut bit = 0;
+    while index != 0 {
+        if index & 1 != 0 {
+            v ^= SOBOL_MATRICES[dimension][bit];
+        }
+        index >>= 1;
+        bit += 1;
+    }
+    v
+}
+
+fn sobol_scrambled(index: u32, dimension: usize, seed: u32) -> f64 {
+    let v = nested_uniform_scramble(sobol(index, dimension), seed);
+    f64::min(v as f64 / 4294967296.0, ONE_MINUS_EPSILON)
+}
+
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x ^= x.wrapping_mul(0x3d20adea);
+    x = x.wrapping_add(seed);
+    x = x.wrapping_mul((seed >> 16) | 1);
+    x ^= x.wrapping_mul(0x05526c56);
+    x ^= x.wrapping_mul(0x53a22864);
+    x
+}
+
+// Owen scrambling of a 32 bit fixed point value.
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+fn owen_scrambled_radical_inverse(base: u64, mut a: u64, hash: u64) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let mut inv_base_m = 1.0;
+    let mut reversed_digits: u64 = 0;
+    // Keep going until the remaining digits no longer change the float.
+    while 1.0 - inv_base_m < 1.0 {
+        let next = a / base;
+        let digit = a - next * base;
+        let digit_hash = mix_bits(hash ^ reversed_digits);
+        let digit = permutation_element(digit as usize, base as usize, digit_hash) as u64;
+        reversed_digits = reversed_digits.wrapping_mul(base).wrapping_add(digit);
+        inv_base_m *= inv_base;
+        a = next;
+    }
+    f64::min(inv_base_m * reversed_digits as f64, ONE_MINUS_EPSILON)
+}
+
+// Element `i` of a random permutation of 0..n chosen by `seed`, computed without
+// storing the permutation (Kensler 2013, "Correlated Multi-Jittered Sampling").
+fn permutation_element(i: usize, n: usize, seed: u64) -> usize {
+    if n <= 1 {
+        return 0;
+    }
+    let (mut i, l, p) = (i as u32, n as u32, seed as u32);
+    let mut w = l - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < l {
+            break;
+        }
+    }
+    ((i.wrapping_add(p)) % l) as usize
+}
+
+pub fn mix_bits(mut v: u64) -> u64 {
+    v ^= v >> 31;
+    v = v.wrapping_mul(0x7fb5_d329_728e_a185);
+    v ^= v >> 27;
+    v = v.wrapping_mul(0x81da_def4_bc2d_d44d);
+    v ^= v >> 33;
+    v
+}
+
+pub fn hash(values: &[u64]) -> u64 {
+    values
+        .iter()
+        .fold(0x243f_6a88_85a3_08d3, |h, v| mix_bits(h ^ mix_bits(*v)))
+}
+
+fn to_unit_float(bits: u64) -> f64 {
+    // Use the top 53 bits so the result is exactly representable and < 1.
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+// Warps from the unit square to the shapes the renderer samples.
+
+pub fn sample_unit_disk(u: (f64, f64)) -> Vec3 {
+    // Shirley-Chiu concentric mapping keeps the strata of `u` compact.
+    let (ox, oy) = (2.0 * u.0 - 1.0, 2.0 * u.1 - 1.0);
+    if ox == 0.0 && oy == 0.0 {
+        return Vec3::default();
+    }
+    let (r, theta) = if ox.abs() > oy.abs() {
+        (ox, PI / 4.0 * (oy / ox))
+    } else {
+        (oy, PI / 2.0 - PI / 4.0 * (ox / oy))
+    };
+    Vec3::new(r * theta.cos(), r * theta.sin(), 0.0)
+}
+
+pub fn sample_unit_sphere(u: (f64, f64)) -> Vec3 {
+    let z = 1.0 - 2.0 * u.0;
+    let r = f64::max(0.0, 1.0 - z * z).sqrt();
+    let phi = 2.0 * PI * u.1;
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+pub fn sample_unit_ball(u: (f64, f64), u_radius: f64) -> Point3 {
+    sample_unit_sphere(u) * u_radius.cbrt()
+}
+
+// Cosine-weighted direction about +z (Malley's method), pdf cos(theta) / pi.
+pub fn sample_cosine_hemisphere(u: (f64, f64)) -> Vec3 {
+    let d = sample_unit_disk(u);
+    let z = f64::max(0.0, 1.0 - d.x * d.x - d.y * d.y).sqrt();
+    Vec3::new(d.x, d.y, z)
+}
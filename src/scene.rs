@@ -0,0 +1,56 @@
+use crate::{
+    aabb::Aabb,
+    bvh::BVHNode,
+    hittable::{HitRecord, Hittable},
+    hittable_list::HittableList,
+    ray::Ray,
+};
+
+// Top level container for a world. Objects with a bounding box go in a BVH;
+// unbounded ones, such as infinite planes, are kept aside and tested against
+// every ray.
+pub struct Scene {
+    bvh: Option<BVHNode>,
+    unbounded: HittableList,
+}
+
+impl Scene {
+    pub fn new(list: &HittableList, time0: f64, time1: f64) -> Scene {
+        let mut bounded = HittableList::default();
+        let mut unbounded = HittableList::default();
+        let mut output_box = Aabb::new(Default::default(), Default::default());
+        for object in &list.objects {
+            if object.bounding_box(time0, time1, &mut output_box) {
+                bounded.add(object.clone());
+            } else {
+                unbounded.add(object.clone());
+            }
+        }
+        // Every object in `bounded` has a box, so only an empty list can fail.
+        let bvh = BVHNode::new(&bounded, time0, time1).ok();
+        Scene { bvh, unbounded }
+    }
+}
+
+impl Hittable for Scene {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let hit_bounded = self
+            .bvh
+            .as_ref()
+            .is_some_and(|bvh| bvh.hit(r, t_min, t_max, rec));
+        if self.unbounded.objects.is_empty() {
+            return hit_bounded;
+        }
+        let closest = if hit_bounded { rec.t } else { t_max };
+        self.unbounded.hit(r, t_min, closest, rec) || hit_bounded
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64, output_box: &mut Aabb) -> bool {
+        match &self.bvh {
+            Some(bvh) if self.unbounded.objects.is_empty() => {
+                bvh.bounding_box(time0, time1, output_box)
+            }
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,308 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb, hittable::*, material::Material, onb::Onb, ray::Ray, sphere::get_sphere_uv, vec3::*,
+};
+
+// Signed distance to a surface: negative inside, positive outside, and never
+// more than the true distance, so a sphere of that radius around any point is
+// empty. Closures from points to distances are distance functions too.
+pub trait Sdf {
+    fn distance(&self, p: Point3) -> f64;
+}
+
+impl<F: Fn(Point3) -> f64> Sdf for F {
+    fn distance(&self, p: Point3) -> f64 {
+        self(p)
+    }
+}
+
+// Surface where a distance function is zero, found by sphere tracing: the ray
+// repeatedly advances by the distance to the surface until it is within
+// `epsilon` of it. The surface must lie inside `bbox`, which also lets it go
+// in a `BVHNode`. Distance functions that overestimate, such as `Twist`, need
+// a step scale below one. Normals come from the gradient of the distance; u
+// and v are the spherical coordinates of the normal.
+pub struct SdfHittable {
+    sdf: Arc<dyn Sdf + Send + Sync>,
+    bbox: Aabb,
+    mat_ptr: Arc<dyn Material + Sync + Send>,
+    id: u32,
+    material_id: u32,
+    epsilon: f64,
+    step_scale: f64,
+    max_steps: u32,
+}
+
+impl SdfHittable {
+    pub fn new(
+        sdf: Arc<dyn Sdf + Send + Sync>,
+        bbox: Aabb,
+        mat_ptr: Arc<dyn Material + Sync + Send>,
+    ) -> SdfHittable {
+        SdfHittable {
+            sdf,
+            bbox,
+            id: new_object_id(),
+            material_id: material_id(&mat_ptr),
+            mat_ptr,
+            epsilon: 1e-4,
+            step_scale: 1.0,
+            max_steps: 512,
+        }
+    }
+
+    pub fn with_epsilon(mut self, epsilon: f64) -> SdfHittable {
+        self.epsilon = epsilon;
+        self
+    }
+
+    pub fn with_step_scale(mut self, step_scale: f64) -> SdfHittable {
+        self.step_scale = step_scale;
+        self
+    }
+
+    pub fn with_max_steps(mut self, max_steps: u32) -> SdfHittable {
+        self.max_steps = max_steps;
+        self
+    }
+
+    // Central differences on the corners of a tetrahedron, four evaluations
+    // instead of six.
+    fn gradient(&self, p: Point3) -> Vec3 {
+        let h = self.epsilon;
+        let k = [
+            Vec3::new(1.0, -1.0, -1.0),
+            Vec3::new(-1.0, -1.0, 1.0),
+            Vec3::new(-1.0, 1.0, -1.0),
+            Vec3::new(1.0, 1.0, 1.0),
+        ];
+        k.iter().fold(Vec3::default(), |g, k| {
+            g + *k * self.sdf.distance(p + *k * h)
+        })
+    }
+}
+
+impl Hittable for SdfHittable {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let (t0, t1) = match self.bbox.clip(r, t_min, t_max) {
+            Some(range) => range,
+            None => return false,
+        };
+        // March in units of distance, which is what the field measures.
+        let length = r.direction().length();
+        let d = *r.direction() / length;
+        let origin = *r.origin();
+        let (mut t, t_end) = (t0 * length, t1 * length);
+        // A ray leaving the surface starts within epsilon of it; move it clear
+        // so it doesn't hit where it started.
+        let mut steps = 0;
+        let mut start = self.sdf.distance(origin + d * t);
+        while start.abs() < self.epsilon && steps < self.max_steps {
+            t += self.epsilon;
+            start = self.sdf.distance(origin + d * t);
+            steps += 1;
+        }
+        // Rays that start inside, such as refracted ones, march on the
+        // magnitude of the distance until they get out.
+        let side = start.signum();
+        loop {
+            let dist = side * self.sdf.distance(origin + d * t);
+            if dist < self.epsilon {
+                break;
+            }
+            t += dist * self.step_scale;
+            steps += 1;
+            if t > t_end || steps >= self.max_steps {
+                return false;
+            }
+        }
+
+        let outward_normal = self.gradient(origin + d * t);
+        if outward_normal.near_zero() {
+            return false;
+        }
+        let outward_normal = outward_normal.unit_vector();
+        let frame = Onb::build_from_w(&outward_normal);
+        rec.t = t / length;
+        rec.p = r.at(rec.t);
+        rec.set_face_normal(r, &outward_normal);
+        (rec.u, rec.v) = get_sphere_uv(&outward_normal);
+        (rec.dpdu, rec.dpdv) = (frame.u, frame.v);
+        rec.mat_ptr = self.mat_ptr.clone();
+        rec.object_id = self.id;
+        rec.material_id = self.material_id;
+        rec.velocity = Vec3::default();
+        true
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64, output_box: &mut Aabb) -> bool {
+        *output_box = self.bbox;
+        true
+    }
+}
+
+// Sphere of the given radius around the origin.
+pub struct SphereSdf {
+    pub radius: f64,
+}
+
+impl Sdf for SphereSdf {
+    fn distance(&self, p: Point3) -> f64 {
+        p.length() - self.radius
+    }
+}
+
+// Box around the origin with the given half extents, its edges rounded by
+// `radius`.
+pub struct BoxSdf {
+    pub half_extents: Vec3,
+    pub radius: f64,
+}
+
+impl Sdf for BoxSdf {
+    fn distance(&self, p: Point3) -> f64 {
+        let q = Vec3::new(p.x.abs(), p.y.abs(), p.z.abs()) - self.half_extents
+            + Vec3::new(self.radius, self.radius, self.radius);
+        let outside = Vec3::new(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0)).length();
+        outside + q.x.max(q.y).max(q.z).min(0.0) - self.radius
+    }
+}
+
+// Torus around the y axis.
+pub struct TorusSdf {
+    pub major_radius: f64,
+    pub minor_radius: f64,
+}
+
+impl Sdf for TorusSdf {
+    fn distance(&self, p: Point3) -> f64 {
+        let ring = (p.x * p.x + p.z * p.z).sqrt() - self.major_radius;
+        (ring * ring + p.y * p.y).sqrt() - self.minor_radius
+    }
+}
+
+// Distance estimate for the power 8 Mandelbulb of White and Nylander, which
+// fits in a sphere of radius 1.2 around the origin.
+pub struct Mandelbulb {
+    pub power: f64,
+    pub iterations: u32,
+}
+
+impl Default for Mandelbulb {
+    fn default() -> Self {
+        Mandelbulb {
+            power: 8.0,
+            iterations: 12,
+        }
+    }
+}
+
+impl Sdf for Mandelbulb {
+    fn distance(&self, p: Point3) -> f64 {
+        let mut z = p;
+        let mut dr = 1.0;
+        let mut r = z.length();
+        for _ in 0..self.iterations {
+            if r > 2.0 {
+                break;
+            }
+            let theta = (z.z / r).clamp(-1.0, 1.0).acos() * self.power;
+            let phi = z.y.atan2(z.x) * self.power;
+            dr = r.powf(self.power - 1.0) * self.power * dr + 1.0;
+            let zr = r.powf(self.power);
+            z = Vec3::new(
+                theta.sin() * phi.cos(),
+                theta.sin() * phi.sin(),
+                theta.cos(),
+            ) * zr
+                + p;
+            r = z.length();
+        }
+        if r == 0.0 {
+            return 0.0;
+        }
+        0.5 * r.ln() * r / dr
+    }
+}
+
+// Union of two shapes blended over a distance of about `k` where they meet
+// (polynomial smooth minimum).
+pub struct SmoothUnion {
+    pub a: Arc<dyn Sdf + Send + Sync>,
+    pub b: Arc<dyn Sdf + Send + Sync>,
+    pub k: f64,
+}
+
+impl Sdf for SmoothUnion {
+    fn distance(&self, p: Point3) -> f64 {
+        let (da, db) = (self.a.distance(p), self.b.distance(p));
+        if self.k <= 0.0 {
+            return da.min(db);
+        }
+        let h = (0.5 + 0.5 * (db - da) / self.k).clamp(0.0, 1.0);
+        db + (da - db) * h - self.k * h * (1.0 - h)
+    }
+}
+
+// Copies of a shape centred on the points of a lattice with the given
+// spacing along each axis; a spacing of zero doesn't repeat along that axis.
+// The shape should fit in one cell.
+pub struct Repeat {
+    pub sdf: Arc<dyn Sdf + Send + Sync>,
+    pub spacing: Vec3,
+}
+
+impl Sdf for Repeat {
+    fn distance(&self, p: Point3) -> f64 {
+        let wrap = |x: f64, s: f64| if s > 0.0 { x - s * (x / s).round() } else { x };
+        self.sdf.distance(Vec3::new(
+            wrap(p.x, self.spacing.x),
+            wrap(p.y, self.spacing.y),
+            wrap(p.z, self.spacing.z),
+        ))
+    }
+}
+
+// A shape twisted around the y axis by `rate` radians per unit of height. The
+// twist stretches space, so distances overestimate by up to
+// sqrt(1 + (rate * r)^2) at distance r from the axis; trace it with a step
+// scale of about the inverse.
+pub struct Twist {
+    pub sdf: Arc<dyn Sdf + Send + Sync>,
+    pub rate: f64,
+}
+
+impl Sdf for Twist {
+    fn distance(&self, p: Point3) -> f64 {
+        let (sin, cos) = (self.rate * p.y).sin_cos();
+        self.sdf.distance(Vec3::new(
+            cos * p.x + sin * p.z,
+            p.y,
+            -sin * p.x + cos * p.z,
+        ))
+    }
+}
+
+pub struct Translate {
+    pub sdf: Arc<dyn Sdf + Send + Sync>,
+    pub offset: Vec3,
+}
+
+impl Sdf for Translate {
+    fn distance(&self, p: Point3) -> f64 {
+        self.sdf.distance(p - self.offset)
+    }
+}
+
+// Uniform scaling about the origin.
+pub struct Scale {
+    pub sdf: Arc<dyn Sdf + Send + Sync>,
+    pub factor: f64,
+}
+
+impl Sdf for Scale {
+    fn distance(&self, p: Point3) -> f64 {
+        self.sdf.distance(p / self.factor) * self.factor
+    }
+}
@@ -0,0 +1,196 @@
+use std::sync::OnceLock;
+
+use crate::vec3::Color;
+
+// Visible range sampled by the spectral mode, in nanometres.
+pub const LAMBDA_MIN: f64 = 360.0;
+pub const LAMBDA_MAX: f64 = 830.0;
+
+// Wavelengths carried by one spectral path: a hero wavelength and two more
+// rotated by a third of the range (Wilkie et al. 2014). Spectral quantities
+// along the path are stored in the three channels of a `Color`, one per
+// wavelength.
+#[derive(Debug, Clone, Copy)]
+pub struct SampledWavelengths {
+    pub lambda: [f64; 3],
+    pub pdf: [f64; 3],
+}
+
+impl SampledWavelengths {
+    pub fn sample_uniform(u: f64) -> SampledWavelengths {
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let mut lambda = [0.0; 3];
+        for (i, l) in lambda.iter_mut().enumerate() {
+            *l = LAMBDA_MIN + (u + i as f64 / 3.0).fract() * range;
+        }
+        SampledWavelengths {
+            lambda,
+            pdf: [1.0 / range; 3],
+        }
+    }
+
+    pub fn hero(&self) -> f64 {
+        self.lambda[0]
+    }
+
+    // After a wavelength dependent refraction the other wavelengths no longer
+    // follow the hero's path; only the hero carries on.
+    pub fn terminate_secondary(&mut self) {
+        if self.secondary_terminated() {
+            return;
+        }
+        self.pdf = [self.pdf[0] / 3.0, 0.0, 0.0];
+    }
+
+    pub fn secondary_terminated(&self) -> bool {
+        self.pdf[1] == 0.0
+    }
+
+    // Reflectance spectrum of an RGB albedo, sampled at these wavelengths.
+    pub fn reflectance(&self, rgb: Color) -> Color {
+        Color::new(
+            rgb_to_spectrum(rgb, self.lambda[0]),
+            rgb_to_spectrum(rgb, self.lambda[1]),
+            rgb_to_spectrum(rgb, self.lambda[2]),
+        )
+    }
+
+    // Monte Carlo estimate of the linear sRGB colour of spectral radiance
+    // sampled at these wavelengths.
+    pub fn to_rgb(&self, radiance: Color) -> Color {
+        let samples = [radiance.x, radiance.y, radiance.z];
+        let mut xyz = Color::default();
+        for ((lambda, pdf), sample) in self.lambda.iter().zip(self.pdf).zip(samples) {
+            if pdf > 0.0 {
+                xyz += cie_xyz(*lambda) * (sample / pdf);
+            }
+        }
+        let tables = tables();
+        let rgb = xyz_to_srgb(xyz / (3.0 * tables.y_integral));
+        Color::new(
+            rgb.x / tables.white.x,
+            rgb.y / tables.white.y,
+            rgb.z / tables.white.z,
+        )
+    }
+}
+
+// Index of refraction as a function of wavelength, with coefficients in
+// micrometres as they are usually tabulated.
+#[derive(Debug, Clone, Copy)]
+pub enum Dispersion {
+    // n = a + b / lambda^2
+    Cauchy { a: f64, b: f64 },
+    // n^2 = 1 + sum b_i lambda^2 / (lambda^2 - c_i)
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Dispersion {
+    pub fn ior(&self, lambda: f64) -> f64 {
+        let l2 = (lambda * 1e-3) * (lambda * 1e-3);
+        match self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => {
+                let sum: f64 = (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum();
+                (1.0 + sum).sqrt()
+            }
+        }
+    }
+}
+
+// CIE 1931 2 degree colour matching functions, multi-lobe fit of Wyman, Sloan
+// and Shirley 2013.
+pub fn cie_xyz(lambda: f64) -> Color {
+    let g = |mu: f64, sigma1: f64, sigma2: f64| {
+        let t = (lambda - mu) / if lambda < mu { sigma1 } else { sigma2 };
+        (-0.5 * t * t).exp()
+    };
+    Color::new(
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    )
+}
+
+pub fn xyz_to_srgb(xyz: Color) -> Color {
+    Color::new(
+        3.2404542 * xyz.x - 1.5371385 * xyz.y - 0.4985314 * xyz.z,
+        -0.9692660 * xyz.x + 1.8760108 * xyz.y + 0.0415560 * xyz.z,
+        0.0556434 * xyz.x - 0.2040259 * xyz.y + 1.0572252 * xyz.z,
+    )
+}
+
+struct Tables {
+    // Integral of the y matching function, so a constant spectrum of 1 has Y = 1.
+    y_integral: f64,
+    // sRGB of that constant spectrum; dividing by it keeps white surfaces white.
+    white: Color,
+}
+
+fn tables() -> &'static Tables {
+    static TABLES: OnceLock<Tables> = OnceLock::new();
+    TABLES.get_or_init(|| {
+        let mut xyz = Color::default();
+        let mut lambda = LAMBDA_MIN;
+        while lambda <= LAMBDA_MAX {
+            xyz += cie_xyz(lambda);
+            lambda += 1.0;
+        }
+        Tables {
+            y_integral: xyz.y,
+            white: xyz_to_srgb(xyz / xyz.y),
+        }
+    })
+}
+
+// RGB to spectrum conversion of Smits 1999: the colour is built from white and
+// the two primaries or secondaries closest to it, each a piecewise constant
+// spectrum over ten bins from 380 to 720 nm. Unlike table based upsampling it
+// needs no precomputed data, and albedos in [0, 1] stay in [0, 1].
+fn rgb_to_spectrum(c: Color, lambda: f64) -> f64 {
+    let bin = (((lambda - 380.0) / 34.0).floor().max(0.0) as usize).min(9);
+    let s = |curve: &[f64; 10]| curve[bin];
+    let (r, g, b) = (c.x, c.y, c.z);
+    if r <= g && r <= b {
+        r * s(&WHITE)
+            + if g <= b {
+                (g - r) * s(&CYAN) + (b - g) * s(&BLUE)
+            } else {
+                (b - r) * s(&CYAN) + (g - b) * s(&GREEN)
+            }
+    } else if g <= r && g <= b {
+        g * s(&WHITE)
+            + if r <= b {
+                (r - g) * s(&MAGENTA) + (b - r) * s(&BLUE)
+            } else {
+                (b - g) * s(&MAGENTA) + (r - b) * s(&RED)
+            }
+    } else {
+        b * s(&WHITE)
+            + if r <= g {
+                (r - b) * s(&YELLOW) + (g - r) * s(&GREEN)
+            } else {
+                (g - b) * s(&YELLOW) + (r - g) * s(&RED)
+            }
+    }
+}
+
+const WHITE: [f64; 10] = [1.0, 1.0, 0.9999, 0.9993, 0.9992, 0.9998, 1.0, 1.0, 1.0, 1.0];
+const CYAN: [f64; 10] = [
+    0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0, 0.0, 0.0,
+];
+const MAGENTA: [f64; 10] = [
+    1.0, 1.0, 0.9685, 0.2229, 0.0, 0.0458, 0.8369, 1.0, 1.0, 0.9959,
+];
+const YELLOW: [f64; 10] = [
+    0.0001, 0.0, 0.1088, 0.6651, 1.0, 1.0, 0.9996, 0.9586, 0.9685, 0.9840,
+];
+const RED: [f64; 10] = [
+    0.1012, 0.0515, 0.0, 0.0, 0.0, 0.0, 0.8325, 1.0149, 1.0149, 1.0149,
+];
+const GREEN: [f64; 10] = [
+    0.0, 0.0, 0.0273, 0.7937, 1.0, 0.9418, 0.1719, 0.0, 0.0, 0.0025,
+];
+const BLUE: [f64; 10] = [
+    1.0, 1.0, 0.8916, 0.3323, 0.0, 0.0, 0.0003, 0.0369, 0.0483, 0.0496,
+];
@@ -4,6 +4,7 @@ use crate::aabb::Aabb;
 use crate::hittable::*;
 use crate::material::Material;
 use crate::ray::Ray;
+use crate::rtweekend::PI;
 use crate::vec3::*;
 
 #[derive(Clone)]
@@ -11,6 +12,8 @@ pub struct Sphere {
     center: Point3,
     radius: f64,
     mat_ptr: Arc<dyn Material + Sync + Send>,
+    id: u32,
+    material_id: u32,
 }
 
 impl Sphere {
@@ -18,6 +21,8 @@ impl Sphere {
         Sphere {
             center: cen,
             radius: r,
+            id: new_object_id(),
+            material_id: material_id(&mat_ptr),
             mat_ptr,
         }
     }
@@ -48,7 +53,12 @@ impl Hittable for Sphere {
         rec.p = r.at(rec.t);
         let outward_normal = (rec.p - self.center) / self.radius;
         rec.set_face_normal(r, &outward_normal);
+        (rec.u, rec.v) = get_sphere_uv(&outward_normal);
+        (rec.dpdu, rec.dpdv) = get_sphere_tangents(&outward_normal, self.radius);
         rec.mat_ptr = self.mat_ptr.clone();
+        rec.object_id = self.id;
+        rec.material_id = self.material_id;
+        rec.velocity = Vec3::default();
         true
     }
 
@@ -60,3 +70,21 @@ impl Hittable for Sphere {
         true
     }
 }
+
+// p: a given point on the sphere of radius one, centered at the origin.
+// u: returned value [0,1] of angle around the Y axis from X=-1.
+// v: returned value [0,1] of angle from Y=-1 to Y=+1.
+pub fn get_sphere_uv(p: &Point3) -> (f64, f64) {
+    let theta = f64::acos(-p.y);
+    let phi = f64::atan2(-p.z, p.x) + PI;
+    (phi / (2.0 * PI), theta / PI)
+}
+
+// Derivatives of the point with unit normal p on a sphere of the given radius
+// with respect to the (u, v) of `get_sphere_uv`.
+pub fn get_sphere_tangents(p: &Point3, radius: f64) -> (Vec3, Vec3) {
+    let dpdu = Vec3::new(p.z, 0.0, -p.x) * (2.0 * PI * radius);
+    let sin_theta = f64::max(1e-9, 1.0 - p.y * p.y).sqrt();
+    let dpdv = Vec3::new(-p.x * p.y / sin_theta, sin_theta, -p.y * p.z / sin_theta) * (PI * radius);
+    (dpdu, dpdv)
+}
@@ -0,0 +1,142 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{material::Material, triangle::TriangleMesh, vec3::Point3};
+
+// Polygon mesh with faces of any size, such as a Catmull-Clark control cage.
+// Faces list their vertices counter-clockwise seen from outside.
+#[derive(Debug, Clone, Default)]
+pub struct PolygonMesh {
+    pub positions: Vec<Point3>,
+    pub faces: Vec<Vec<usize>>,
+}
+
+impl PolygonMesh {
+    pub fn new(positions: Vec<Point3>, faces: Vec<Vec<usize>>) -> PolygonMesh {
+        PolygonMesh { positions, faces }
+    }
+
+    // One step of Catmull-Clark subdivision, splitting every face into quads.
+    // Edges with a single face are boundaries, which follow the cubic B-spline
+    // through their vertices, so open cages keep their outline.
+    pub fn catmull_clark(&self) -> PolygonMesh {
+        let nv = self.positions.len();
+        let face_points: Vec<Point3> = self
+            .faces
+            .iter()
+            .map(|face| average(face.iter().map(|&i| self.positions[i])))
+            .collect();
+
+        // Each edge once, with the faces on either side.
+        let mut edge_index = HashMap::new();
+        let mut edges: Vec<((usize, usize), Vec<usize>)> = Vec::new();
+        for (f, face) in self.faces.iter().enumerate() {
+            for (i, &a) in face.iter().enumerate() {
+                let b = face[(i + 1) % face.len()];
+                let key = (a.min(b), a.max(b));
+                let e = *edge_index.entry(key).or_insert_with(|| {
+                    edges.push((key, Vec::new()));
+                    edges.len() - 1
+                });
+                edges[e].1.push(f);
+            }
+        }
+
+        let edge_points: Vec<Point3> = edges
+            .iter()
+            .map(|&((a, b), ref faces)| {
+                let (pa, pb) = (self.positions[a], self.positions[b]);
+                if faces.len() == 2 {
+                    (pa + pb + face_points[faces[0]] + face_points[faces[1]]) * 0.25
+                } else {
+                    (pa + pb) * 0.5
+                }
+            })
+            .collect();
+
+        // Faces, edge midpoints and boundary neighbours around each vertex.
+        let mut vertex_faces = vec![Vec::new(); nv];
+        for (f, face) in self.faces.iter().enumerate() {
+            for &i in face {
+                vertex_faces[i].push(f);
+            }
+        }
+        let mut vertex_edges = vec![Vec::new(); nv];
+        let mut boundary_neighbours = vec![Vec::new(); nv];
+        for &((a, b), ref faces) in &edges {
+            let mid = (self.positions[a] + self.positions[b]) * 0.5;
+            vertex_edges[a].push(mid);
+            vertex_edges[b].push(mid);
+            if faces.len() != 2 {
+                boundary_neighbours[a].push(b);
+                boundary_neighbours[b].push(a);
+            }
+        }
+
+        let mut positions: Vec<Point3> = (0..nv)
+            .map(|i| {
+                let p = self.positions[i];
+                match boundary_neighbours[i][..] {
+                    [] if !vertex_faces[i].is_empty() => {
+                        let n = vertex_faces[i].len() as f64;
+                        let f = average(vertex_faces[i].iter().map(|&f| face_points[f]));
+                        let r = average(vertex_edges[i].iter().copied());
+                        (f + r * 2.0 + p * (n - 3.0)) / n
+                    }
+                    [a, b] => (self.positions[a] + p * 6.0 + self.positions[b]) / 8.0,
+                    // Corners of non-manifold or isolated vertices stay put.
+                    _ => p,
+                }
+            })
+            .collect();
+        positions.extend(edge_points);
+        positions.extend(face_points);
+
+        let edge_vertex = |a: usize, b: usize| nv + edge_index[&(a.min(b), a.max(b))];
+        let face_vertex = |f: usize| nv + edges.len() + f;
+        let mut faces = Vec::new();
+        for (f, face) in self.faces.iter().enumerate() {
+            let k = face.len();
+            for i in 0..k {
+                let (prev, v, next) = (face[(i + k - 1) % k], face[i], face[(i + 1) % k]);
+                faces.push(vec![
+                    v,
+                    edge_vertex(v, next),
+                    face_vertex(f),
+                    edge_vertex(prev, v),
+                ]);
+            }
+        }
+        PolygonMesh { positions, faces }
+    }
+
+    // `levels` steps of Catmull-Clark subdivision.
+    pub fn subdivide(&self, levels: u32) -> PolygonMesh {
+        let mut mesh = self.clone();
+        for _ in 0..levels {
+            mesh = mesh.catmull_clark();
+        }
+        mesh
+    }
+
+    // Splits each face into a fan of triangles, with normals averaged across
+    // faces so the result shades smoothly.
+    pub fn triangulate(&self, mat_ptr: Arc<dyn Material + Sync + Send>) -> TriangleMesh {
+        let indices = self
+            .faces
+            .iter()
+            .filter(|face| face.len() >= 3)
+            .flat_map(|face| (1..face.len() - 1).map(move |i| [face[0], face[i], face[i + 1]]))
+            .collect();
+        TriangleMesh::new(self.positions.clone(), indices, mat_ptr).with_smooth_normals()
+    }
+}
+
+fn average(points: impl Iterator<Item = Point3>) -> Point3 {
+    let mut sum = Point3::default();
+    let mut n = 0;
+    for p in points {
+        sum += p;
+        n += 1;
+    }
+    sum / n as f64
+}
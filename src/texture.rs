@@ -0,0 +1,119 @@
+use std::sync::Arc;
+
+use image::ImageResult;
+
+use crate::vec3::{Color, Point3};
+
+pub trait Texture {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color;
+
+    // Scalar parameters (roughness, metallic, ...) read the first channel.
+    fn scalar(&self, u: f64, v: f64, p: &Point3) -> f64 {
+        self.value(u, v, p).x
+    }
+}
+
+pub struct SolidColor {
+    pub color_value: Color,
+}
+
+impl SolidColor {
+    pub fn new(color_value: Color) -> SolidColor {
+        SolidColor { color_value }
+    }
+
+    // Texture holding the same value in every channel.
+    pub fn uniform(value: f64) -> Arc<dyn Texture + Send + Sync> {
+        Arc::new(SolidColor::new(Color::new(value, value, value)))
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+        self.color_value
+    }
+}
+
+pub struct CheckerTexture {
+    pub odd: Arc<dyn Texture + Send + Sync>,
+    pub even: Arc<dyn Texture + Send + Sync>,
+    pub scale: f64,
+}
+
+impl CheckerTexture {
+    pub fn new(
+        odd: Arc<dyn Texture + Send + Sync>,
+        even: Arc<dyn Texture + Send + Sync>,
+        scale: f64,
+    ) -> CheckerTexture {
+        CheckerTexture { odd, even, scale }
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        let sines = (self.scale * p.x).sin() * (self.scale * p.y).sin() * (self.scale * p.z).sin();
+        if sines < 0.0 {
+            self.odd.value(u, v, p)
+        } else {
+            self.even.value(u, v, p)
+        }
+    }
+}
+
+// Texture read from an image file. Colour images are converted from sRGB-ish
+// gamma 2 to linear, matching how the renderer writes images; data such as
+// normal and height maps is loaded with `linear` and used as stored.
+pub struct ImageTexture {
+    data: Vec<Color>,
+    width: u32,
+    height: u32,
+}
+
+impl ImageTexture {
+    pub fn new(path: &str) -> ImageResult<ImageTexture> {
+        ImageTexture::load(path, true)
+    }
+
+    pub fn linear(path: &str) -> ImageResult<ImageTexture> {
+        ImageTexture::load(path, false)
+    }
+
+    fn load(path: &str, gamma: bool) -> ImageResult<ImageTexture> {
+        let img = image::open(path)?.to_rgb8();
+        let (width, height) = img.dimensions();
+        let data = img
+            .pixels()
+            .map(|p| {
+                let c = |v: u8| {
+                    let v = v as f64 / 255.0;
+                    if gamma {
+                        v * v
+                    } else {
+                        v
+                    }
+                };
+                Color::new(c(p[0]), c(p[1]), c(p[2]))
+            })
+            .collect();
+        Ok(ImageTexture {
+            data,
+            width,
+            height,
+        })
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: &Point3) -> Color {
+        if self.data.is_empty() {
+            return Color::new(0.0, 1.0, 1.0);
+        }
+        // Flip v to image coordinates, wrap outside [0, 1].
+        let u = u.rem_euclid(1.0);
+        let v = 1.0 - v.rem_euclid(1.0);
+        let i = u32::min((u * self.width as f64) as u32, self.width - 1);
+        let j = u32::min((v * self.height as f64) as u32, self.height - 1);
+        self.data[(j * self.width + i) as usize]
+    }
+}
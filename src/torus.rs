@@ -0,0 +1,114 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb, disk::azimuth, hittable::*, material::Material, onb::Onb, ray::Ray,
+    roots::polynomial_roots, rtweekend::PI, vec3::*,
+};
+
+// Torus around `axis` through `center`: a tube of radius `minor_radius`
+// swept around a circle of radius `major_radius`. u is the angle around the
+// axis and v the angle around the tube, starting on its outer equator.
+#[derive(Clone)]
+pub struct Torus {
+    center: Point3,
+    frame: Onb,
+    major_radius: f64,
+    minor_radius: f64,
+    mat_ptr: Arc<dyn Material + Sync + Send>,
+    id: u32,
+    material_id: u32,
+}
+
+impl Torus {
+    pub fn new(
+        center: Point3,
+        axis: Vec3,
+        major_radius: f64,
+        minor_radius: f64,
+        mat_ptr: Arc<dyn Material + Sync + Send>,
+    ) -> Torus {
+        Torus {
+            center,
+            frame: Onb::build_from_w(&axis),
+            major_radius,
+            minor_radius,
+            id: new_object_id(),
+            material_id: material_id(&mat_ptr),
+            mat_ptr,
+        }
+    }
+}
+
+impl Hittable for Torus {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let length = r.direction().length();
+        let d = self.frame.to_local(*r.direction() / length);
+        let mut o = self.frame.to_local(*r.origin() - self.center);
+
+        // Clip the ray to the bounding sphere and solve from where it enters,
+        // which keeps the coefficients well conditioned for distant rays.
+        let outer = self.major_radius + self.minor_radius;
+        let half_b = dot(&o, &d);
+        let discriminant = half_b * half_b - (o.length_squared() - outer * outer);
+        if discriminant < 0.0 {
+            return false;
+        }
+        let sqrtd = discriminant.sqrt();
+        let t0 = f64::max(-half_b - sqrtd, t_min * length);
+        let t1 = f64::min(-half_b + sqrtd, t_max * length);
+        if t0 > t1 {
+            return false;
+        }
+        o += d * t0;
+
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + y^2) along p = o + t d, |d| = 1.
+        let r2 = self.major_radius * self.major_radius;
+        let e = o.length_squared() + r2 - self.minor_radius * self.minor_radius;
+        let f = dot(&o, &d);
+        let coeffs = [
+            e * e - 4.0 * r2 * (o.x * o.x + o.y * o.y),
+            4.0 * f * e - 8.0 * r2 * (o.x * d.x + o.y * d.y),
+            4.0 * f * f + 2.0 * e - 4.0 * r2 * (d.x * d.x + d.y * d.y),
+            4.0 * f,
+            1.0,
+        ];
+        let t = match polynomial_roots(&coeffs, 0.0, t1 - t0).first() {
+            Some(t) => *t,
+            None => return false,
+        };
+
+        let p = o + d * t;
+        let phi = azimuth(p);
+        let radial = Vec3::new(phi.cos(), phi.sin(), 0.0);
+        let tube = p - radial * self.major_radius;
+        let theta = tube.z.atan2(dot(&tube, &radial)).rem_euclid(2.0 * PI);
+        let (sin_theta, cos_theta) = theta.sin_cos();
+        let ring = self.major_radius + self.minor_radius * cos_theta;
+        rec.t = (t + t0) / length;
+        rec.p = r.at(rec.t);
+        rec.set_face_normal(r, &self.frame.local(tube.unit_vector()));
+        (rec.u, rec.v) = (phi / (2.0 * PI), theta / (2.0 * PI));
+        rec.dpdu = self
+            .frame
+            .local(Vec3::new(-radial.y, radial.x, 0.0) * (2.0 * PI * ring));
+        rec.dpdv = self.frame.local(
+            Vec3::new(-sin_theta * radial.x, -sin_theta * radial.y, cos_theta)
+                * (2.0 * PI * self.minor_radius),
+        );
+        rec.mat_ptr = self.mat_ptr.clone();
+        rec.object_id = self.id;
+        rec.material_id = self.material_id;
+        rec.velocity = Vec3::default();
+        true
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64, output_box: &mut Aabb) -> bool {
+        let w = self.frame.w;
+        let outer = self.major_radius + self.minor_radius;
+        let extent =
+            |a: f64| outer * f64::max(0.0, 1.0 - a * a).sqrt() + self.minor_radius * a.abs();
+        let e = Vec3::new(extent(w.x), extent(w.y), extent(w.z));
+        *output_box = Aabb::new(self.center - e, self.center + e);
+        true
+    }
+}
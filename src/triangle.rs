@@ -0,0 +1,387 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Error, ErrorKind},
+    sync::Arc,
+};
+
+use crate::{
+    aabb::Aabb, hittable::*, hittable_list::HittableList, layered::VertexColored,
+    material::Material, onb::Onb, ply::Ply, ray::Ray, vec3::*,
+};
+
+// Triangles sharing vertex data. Normals, texture coordinates and colours are
+// optional, either empty or one per vertex; with normals the surface shades
+// smoothly. Without texture coordinates each triangle spans (0, 0), (1, 0),
+// (1, 1). Colours are interpolated into the hit record for `VertexColored`.
+pub struct TriangleMesh {
+    pub positions: Vec<Point3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(f64, f64)>,
+    pub colors: Vec<Color>,
+    pub indices: Vec<[usize; 3]>,
+    pub mat_ptr: Arc<dyn Material + Sync + Send>,
+}
+
+impl TriangleMesh {
+    pub fn new(
+        positions: Vec<Point3>,
+        indices: Vec<[usize; 3]>,
+        mat_ptr: Arc<dyn Material + Sync + Send>,
+    ) -> TriangleMesh {
+        TriangleMesh {
+            positions,
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            colors: Vec::new(),
+            indices,
+            mat_ptr,
+        }
+    }
+
+    pub fn with_normals(mut self, normals: Vec<Vec3>) -> TriangleMesh {
+        self.normals = normals;
+        self
+    }
+
+    pub fn with_uvs(mut self, uvs: Vec<(f64, f64)>) -> TriangleMesh {
+        self.uvs = uvs;
+        self
+    }
+
+    pub fn with_colors(mut self, colors: Vec<Color>) -> TriangleMesh {
+        self.colors = colors;
+        self
+    }
+
+    // Vertex normals averaged from the faces around each vertex, weighted by
+    // area.
+    pub fn with_smooth_normals(mut self) -> TriangleMesh {
+        let mut normals = vec![Vec3::default(); self.positions.len()];
+        for [a, b, c] in &self.indices {
+            let (pa, pb, pc) = (self.positions[*a], self.positions[*b], self.positions[*c]);
+            let n = cross(&(pb - pa), &(pc - pa));
+            for i in [a, b, c] {
+                normals[*i] += n;
+            }
+        }
+        self.normals = normals
+            .into_iter()
+            .map(|n| {
+                if n.length_squared() > 0.0 {
+                    n.unit_vector()
+                } else {
+                    n
+                }
+            })
+            .collect();
+        self
+    }
+
+    // Mesh from the vertices and faces of a PLY file, ASCII or binary, as
+    // scanners and most modelling tools write them. Vertex normals (nx, ny,
+    // nz), texture coordinates (u, v or s, t) and colours (red, green, blue)
+    // are read when present; colours are taken as gamma 2 like image textures
+    // and tint `mat_ptr` through `VertexColored`. Polygons are split into fans
+    // of triangles.
+    pub fn load_ply(
+        path: &str,
+        mat_ptr: Arc<dyn Material + Sync + Send>,
+    ) -> io::Result<TriangleMesh> {
+        let ply = Ply::load(path)?;
+        let vertex = ply
+            .element("vertex")
+            .ok_or_else(|| invalid("no vertex element"))?;
+        let vec3s = |names: [&str; 3]| match names.map(|name| vertex.scalar(name)) {
+            [Some(x), Some(y), Some(z)] => Some(
+                (0..vertex.count)
+                    .map(|i| Vec3::new(x[i], y[i], z[i]))
+                    .collect::<Vec<_>>(),
+            ),
+            _ => None,
+        };
+        let positions = vec3s(["x", "y", "z"])
+            .ok_or_else(|| invalid("expected x, y and z vertex properties"))?;
+        let normals = vec3s(["nx", "ny", "nz"]).unwrap_or_default();
+        let uvs = [["u", "v"], ["s", "t"], ["texture_u", "texture_v"]]
+            .iter()
+            .find_map(|[u, v]| Some((vertex.scalar(u)?, vertex.scalar(v)?)))
+            .map(|(u, v)| u.iter().copied().zip(v.iter().copied()).collect())
+            .unwrap_or_default();
+        let colors: Vec<Color> = [
+            ["red", "green", "blue"],
+            ["diffuse_red", "diffuse_green", "diffuse_blue"],
+        ]
+        .iter()
+        .find_map(|names| match names.map(|name| vertex.normalized(name)) {
+            [Some(r), Some(g), Some(b)] => Some(
+                (0..vertex.count)
+                    .map(|i| Color::new(r[i] * r[i], g[i] * g[i], b[i] * b[i]))
+                    .collect(),
+            ),
+            _ => None,
+        })
+        .unwrap_or_default();
+
+        let faces = ply
+            .element("face")
+            .and_then(|face| {
+                face.list("vertex_indices")
+                    .or_else(|| face.list("vertex_index"))
+            })
+            .ok_or_else(|| invalid("expected a face element with vertex_indices"))?;
+        let mut indices = Vec::with_capacity(faces.len());
+        for face in faces {
+            let face = face
+                .iter()
+                .map(|&i| {
+                    if i >= 0.0 && i.fract() == 0.0 && (i as usize) < positions.len() {
+                        Ok(i as usize)
+                    } else {
+                        Err(invalid("vertex index out of range"))
+                    }
+                })
+                .collect::<io::Result<Vec<_>>>()?;
+            for i in 1..face.len().saturating_sub(1) {
+                indices.push([face[0], face[i], face[i + 1]]);
+            }
+        }
+
+        let mat_ptr: Arc<dyn Material + Sync + Send> = if colors.is_empty() {
+            mat_ptr
+        } else {
+            Arc::new(VertexColored::new(mat_ptr))
+        };
+        Ok(TriangleMesh::new(positions, indices, mat_ptr)
+            .with_normals(normals)
+            .with_uvs(uvs)
+            .with_colors(colors))
+    }
+
+    // Mesh from an STL file, ASCII or binary. STL repeats the corners of every
+    // facet, so corners in the same place are merged into one vertex. Facet
+    // normals are ignored in favour of the winding, which STL makes
+    // counter-clockwise seen from outside.
+    pub fn load_stl(
+        path: &str,
+        mat_ptr: Arc<dyn Material + Sync + Send>,
+    ) -> io::Result<TriangleMesh> {
+        let bytes = fs::read(path)?;
+        // Binary files may start with "solid" too, so they are told apart by
+        // their size, 84 bytes of header and count plus 50 per facet.
+        let binary_count = bytes
+            .get(80..84)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize);
+        let corners: Vec<Point3> = match binary_count {
+            Some(count) if bytes.len() == 84 + 50 * count => bytes[84..]
+                .chunks_exact(50)
+                .flat_map(|facet| {
+                    // Skips the normal ahead of the corners.
+                    (1..4).map(move |k| {
+                        let f = |i: usize| {
+                            let b = &facet[12 * k + 4 * i..];
+                            f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64
+                        };
+                        Point3::new(f(0), f(1), f(2))
+                    })
+                })
+                .collect(),
+            _ if bytes.starts_with(b"solid") => {
+                let text = std::str::from_utf8(&bytes).map_err(|_| invalid("not an STL file"))?;
+                let mut words = text.split_ascii_whitespace();
+                let mut corners = Vec::new();
+                while let Some(word) = words.next() {
+                    if word == "vertex" {
+                        let mut f = || -> io::Result<f64> {
+                            words
+                                .next()
+                                .ok_or_else(|| invalid("unexpected end of file"))?
+                                .parse()
+                                .map_err(|_| invalid("expected a number"))
+                        };
+                        corners.push(Point3::new(f()?, f()?, f()?));
+                    }
+                }
+                if corners.len() % 3 != 0 {
+                    return Err(invalid("facets need three vertices"));
+                }
+                corners
+            }
+            _ => return Err(invalid("not an STL file")),
+        };
+
+        let mut positions = Vec::new();
+        let mut merged = HashMap::new();
+        let mut vertex = |p: Point3| {
+            // Adding zero turns -0 into 0, so both merge.
+            let key = [p.x + 0.0, p.y + 0.0, p.z + 0.0].map(f64::to_bits);
+            *merged.entry(key).or_insert_with(|| {
+                positions.push(p);
+                positions.len() - 1
+            })
+        };
+        let indices = corners
+            .chunks_exact(3)
+            .map(|c| [vertex(c[0]), vertex(c[1]), vertex(c[2])])
+            .collect();
+        Ok(TriangleMesh::new(positions, indices, mat_ptr))
+    }
+
+    // One hittable per triangle, ready for a `BVHNode`.
+    pub fn triangles(self) -> HittableList {
+        let id = new_object_id();
+        let material_id = material_id(&self.mat_ptr);
+        let mesh = Arc::new(self);
+        let mut list = HittableList::default();
+        for index in 0..mesh.indices.len() {
+            list.add(Arc::new(Triangle {
+                mesh: mesh.clone(),
+                index,
+                id,
+                material_id,
+            }));
+        }
+        list
+    }
+}
+
+pub struct Triangle {
+    mesh: Arc<TriangleMesh>,
+    index: usize,
+    id: u32,
+    material_id: u32,
+}
+
+impl Triangle {
+    fn uvs(&self) -> [(f64, f64); 3] {
+        let [a, b, c] = self.mesh.indices[self.index];
+        if self.mesh.uvs.is_empty() {
+            [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)]
+        } else {
+            [self.mesh.uvs[a], self.mesh.uvs[b], self.mesh.uvs[c]]
+        }
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let [a, b, c] = self.mesh.indices[self.index];
+        let (p0, p1, p2) = (
+            self.mesh.positions[a],
+            self.mesh.positions[b],
+            self.mesh.positions[c],
+        );
+        let (t, b1, b2) = match intersect_triangle(r, [p0, p1, p2], t_min, t_max) {
+            Some(hit) => hit,
+            None => return false,
+        };
+        let (e1, e2) = (p1 - p0, p2 - p0);
+        let b0 = 1.0 - b1 - b2;
+
+        rec.t = t;
+        rec.p = r.at(t);
+        let mut outward_normal = cross(&e1, &e2).unit_vector();
+        let shading_normal = if self.mesh.normals.is_empty() {
+            None
+        } else {
+            let n =
+                self.mesh.normals[a] * b0 + self.mesh.normals[b] * b1 + self.mesh.normals[c] * b2;
+            // The vertex normals decide which side is outside, so meshes with
+            // inconsistent winding still face the right way.
+            if dot(&n, &outward_normal) < 0.0 {
+                outward_normal = -outward_normal;
+            }
+            (n.length_squared() > 0.0).then(|| n.unit_vector())
+        };
+        rec.set_face_normal(r, &outward_normal);
+        if let Some(n) = shading_normal {
+            rec.set_shading_normal(n);
+        }
+
+        let [uv0, uv1, uv2] = self.uvs();
+        rec.u = b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0;
+        rec.v = b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1;
+        // Solve e1 = du1 dpdu + dv1 dpdv, e2 = du2 dpdu + dv2 dpdv.
+        let (du1, dv1) = (uv1.0 - uv0.0, uv1.1 - uv0.1);
+        let (du2, dv2) = (uv2.0 - uv0.0, uv2.1 - uv0.1);
+        let uv_det = du1 * dv2 - dv1 * du2;
+        (rec.dpdu, rec.dpdv) = if uv_det.abs() > 1e-12 {
+            (
+                (e1 * dv2 - e2 * dv1) / uv_det,
+                (e2 * du1 - e1 * du2) / uv_det,
+            )
+        } else {
+            let frame = Onb::build_from_w(&outward_normal);
+            (frame.u, frame.v)
+        };
+        rec.mat_ptr = self.mesh.mat_ptr.clone();
+        rec.object_id = self.id;
+        rec.material_id = self.material_id;
+        rec.velocity = Vec3::default();
+        rec.vertex_color = (!self.mesh.colors.is_empty()).then(|| {
+            self.mesh.colors[a] * b0 + self.mesh.colors[b] * b1 + self.mesh.colors[c] * b2
+        });
+        true
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64, output_box: &mut Aabb) -> bool {
+        let [a, b, c] = self.mesh.indices[self.index];
+        let (p0, p1, p2) = (
+            self.mesh.positions[a],
+            self.mesh.positions[b],
+            self.mesh.positions[c],
+        );
+        // Padded so triangles in an axis plane don't get a flat box.
+        let pad = Vec3::new(1e-4, 1e-4, 1e-4);
+        *output_box = Aabb::new(
+            Point3::new(
+                p0.x.min(p1.x).min(p2.x),
+                p0.y.min(p1.y).min(p2.y),
+                p0.z.min(p1.z).min(p2.z),
+            ) - pad,
+            Point3::new(
+                p0.x.max(p1.x).max(p2.x),
+                p0.y.max(p1.y).max(p2.y),
+                p0.z.max(p1.z).max(p2.z),
+            ) + pad,
+        );
+        true
+    }
+}
+
+// Möller-Trumbore: t along the ray and the barycentric weights of the second
+// and third vertices.
+pub(crate) fn intersect_triangle(
+    r: &Ray,
+    [p0, p1, p2]: [Point3; 3],
+    t_min: f64,
+    t_max: f64,
+) -> Option<(f64, f64, f64)> {
+    let e1 = p1 - p0;
+    let e2 = p2 - p0;
+    let pvec = cross(r.direction(), &e2);
+    let det = dot(&e1, &pvec);
+    if det.abs() < 1e-12 * e1.length_squared().max(e2.length_squared()) {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+    let tvec = *r.origin() - p0;
+    let b1 = dot(&tvec, &pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+    let qvec = cross(&tvec, &e1);
+    let b2 = dot(r.direction(), &qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+    let t = dot(&e2, &qvec) * inv_det;
+    if t < t_min || t > t_max {
+        return None;
+    }
+    Some((t, b1, b2))
+}
+
+fn invalid(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg.to_string())
+}